@@ -6,7 +6,11 @@ use syn::{parse_macro_input, DeriveInput};
 #[derive(FromDeriveInput, Default)]
 #[darling(default, attributes(data_value))]
 struct DataValueOptions {
-    data_path: String,
+    /// The paths to read the value from, in order of preference.
+    #[darling(multiple)]
+    data_path: Vec<String>,
+    /// Default seconds between updates from the server, if not `DEFAULT_UPDATE_PERIOD`.
+    update_period: Option<u64>,
 }
 
 #[proc_macro_derive(DataValue, attributes(data_value))]
@@ -14,7 +18,15 @@ pub fn dv_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input);
     let opts = DataValueOptions::from_derive_input(&input).expect("Wrong options");
     let DeriveInput { ident, .. } = input;
-    let data_path = opts.data_path;
+    let data_paths = opts.data_path;
+    let raw_value = match data_paths.as_slice() {
+        [data_path] => quote! {
+            communicator.get_fresh_f64_for_path(#data_path.to_string(), self.max_age())
+        },
+        _ => quote! {
+            first_fresh_f64(communicator, &self.paths(), self.max_age())
+        },
+    };
     let answer = quote! {
         fn name(&self) -> String {
             self.name.to_string()
@@ -30,12 +42,19 @@ pub fn dv_derive(input: TokenStream) -> TokenStream {
 
         fn add_config(&mut self, index: usize, ui: &mut Ui) {
             self.display_unit.add_config(index, ui);
+            self.add_settings_config(ui);
+        }
+
+        fn paths(&self) -> Vec<String> {
+            vec![#(#data_paths.to_string()),*]
+        }
+
+        fn raw_value(&self, communicator: &SignalKCommunicator) -> Result<f64, WebSocketError> {
+            #raw_value
         }
 
-        fn fmt_value(&self, communicator: &SignalKCommunicator) -> String {
-            let temp =
-                communicator.get_f64_for_path(#data_path.to_string());
-            self.display_unit.format(temp)
+        fn format_value(&self, value: Result<f64, WebSocketError>) -> String {
+            self.display_unit.format(value)
         }
     };
 
@@ -46,3 +65,34 @@ pub fn dv_derive(input: TokenStream) -> TokenStream {
     };
     output.into()
 }
+
+/// The `max_age_secs` and `update_period_secs` settings every value has.
+#[proc_macro_derive(ValueSettings, attributes(data_value))]
+pub fn vs_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input);
+    let opts = DataValueOptions::from_derive_input(&input).expect("Wrong options");
+    let DeriveInput { ident, .. } = input;
+    let default_period = match opts.update_period {
+        Some(secs) => quote! { Duration::from_secs(#secs) },
+        None => quote! { DEFAULT_UPDATE_PERIOD },
+    };
+    let output = quote! {
+        impl ValueSettings for #ident {
+            fn max_age(&self) -> Duration {
+                Duration::from_secs_f32(self.max_age_secs)
+            }
+
+            fn update_period(&self) -> Duration {
+                self.update_period_secs
+                    .map(Duration::from_secs_f32)
+                    .unwrap_or(#default_period)
+            }
+
+            fn add_settings_config(&mut self, ui: &mut Ui) {
+                add_max_age_config(ui, &mut self.max_age_secs);
+                add_update_period_config(ui, &mut self.update_period_secs, #default_period);
+            }
+        }
+    };
+    output.into()
+}
@@ -1,63 +1,377 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
 use web_time::{Duration, Instant};
 
 use eframe::egui;
 
-use crate::communication::SignalKCommunicator;
-use crate::layouts::LayoutComponent;
+use crate::auth::Authentication;
+use crate::communication::{
+    normalize_server_url, ConnectionSettings, ConnectionState, EndpointMode, PathSubscription,
+    SignalKCommunicator,
+};
+use crate::datatypes::DataValues;
+use crate::datavalues::WaterTemperature;
+use crate::fonts::{font_definitions, DigitFont};
+use crate::layouts::{Layout, LayoutComponent, LayoutKind, SingleValueLayout};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mdns::{ServerBrowser, MDNS_ADDRESS};
+use crate::recording::Recorder;
+use crate::theme::{automatic_theme, theme_subscriptions, DisplayTheme, Palette};
+
+/// Version of the persisted application state, bump when the saved format changes
+/// and add a step to `DisplayApplication::migrate`.
+const CONFIG_VERSION: u32 = 2;
+
+/// The settings needed to reach the server, each read on its own from saved state that can't
+/// be read as a whole.
+#[derive(serde::Deserialize)]
+struct SavedServer {
+    server: String,
+}
+
+#[derive(serde::Deserialize)]
+struct SavedRecentServers {
+    recent_servers: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct SavedConnection {
+    connection: ConnectionSettings,
+}
+
+#[derive(serde::Deserialize)]
+struct SavedAuth {
+    auth: Authentication,
+}
+
+/// How many servers to keep in the recent servers list.
+const MAX_RECENT_SERVERS: usize = 8;
+
+/// Changes to the page list requested from the configuration panel.
+enum PageAction {
+    Add(LayoutKind),
+    Delete(usize),
+    Duplicate(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+}
+
+/// Changes to the server connection requested from the configuration panel.
+enum ServerAction {
+    /// Connect to the server address as typed.
+    Connect(String),
+    /// Connect to the current server again, with new connection settings.
+    Reconnect,
+    /// Stop trying the server being switched to.
+    Cancel,
+}
+
+/// How the display moves between pages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+enum PageRotation {
+    /// Pages only change with the arrows, a swipe or the arrow keys.
+    Manual,
+    /// Every page is shown for the same time.
+    #[default]
+    Interval,
+    /// Every page is shown for its own time.
+    PerPage,
+}
+
+impl PageRotation {
+    const ALL: [PageRotation; 3] = [
+        PageRotation::Manual,
+        PageRotation::Interval,
+        PageRotation::PerPage,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PageRotation::Manual => "Manual",
+            PageRotation::Interval => "Interval",
+            PageRotation::PerPage => "Per page",
+        }
+    }
+}
+
+/// Move `steps` pages forward, or backwards when negative, wrapping around.
+fn step_page(current: usize, steps: isize, count: usize) -> usize {
+    (current as isize + steps).rem_euclid(count as isize) as usize
+}
+
+/// A short description of the connection to the server.
+fn connection_status(communicator: &SignalKCommunicator) -> String {
+    let state = communicator.state();
+    match state.retry_at() {
+        Some(retry_at) => format!(
+            "{}, next attempt in {:.0} s ({} failed)",
+            state.name(),
+            retry_at
+                .saturating_duration_since(Instant::now())
+                .as_secs_f32()
+                .ceil(),
+            communicator.reconnect_attempts()
+        ),
+        None => state.name().to_string(),
+    }
+}
+
+/// The connection state as a coloured badge, with the details as hover text.
+fn status_badge(
+    ui: &mut egui::Ui,
+    communicator: &SignalKCommunicator,
+    palette: &Palette,
+) -> egui::Response {
+    let state = communicator.state();
+    let color = match state {
+        ConnectionState::Streaming => palette.starboard,
+        ConnectionState::Error { .. } => palette.port,
+        ConnectionState::Disconnected => palette.label,
+        _ => palette.accent,
+    };
+    let mut details = connection_status(communicator);
+    if let (Some((error, _)), Some(_)) = (communicator.last_error(), state.retry_at()) {
+        details = format!("{}\n{}", details, error);
+    }
+    ui.add(
+        egui::Button::new(egui::RichText::new(format!("● {}", state.name())).color(color))
+            .frame(false),
+    )
+    .on_hover_text(details)
+}
+
+/// Search for servers on the local network and list them, returns the one picked.
+#[cfg(not(target_arch = "wasm32"))]
+fn server_browser_ui(ui: &mut egui::Ui, browser: &mut ServerBrowser) -> Option<String> {
+    let mut picked = None;
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(!browser.is_searching(), egui::Button::new("Find servers"))
+            .clicked()
+        {
+            browser.start(MDNS_ADDRESS);
+        }
+        if browser.is_searching() {
+            ui.spinner();
+        }
+    });
+    if let Some(error) = browser.error() {
+        ui.label(format!("Search failed: {}", error));
+    }
+    for server in browser.servers() {
+        if ui.button(&server.name).on_hover_text(&server.url).clicked() {
+            picked = Some(server.url.clone());
+        }
+    }
+    picked
+}
+
+/// Start and stop recording the deltas the communicator receives to `record_path`.
+fn recording_ui(
+    ui: &mut egui::Ui,
+    record_path: &mut String,
+    record_error: &mut Option<String>,
+    communicator: &mut SignalKCommunicator,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Record to");
+        let recording = communicator.recorder.is_some();
+        ui.add_enabled(!recording, egui::TextEdit::singleline(record_path));
+        if recording {
+            if ui.button("Stop").clicked() {
+                communicator.recorder = None;
+            }
+        } else if ui.button("Record").clicked() {
+            match Recorder::create(record_path) {
+                Ok(recorder) => {
+                    communicator.recorder = Some(recorder);
+                    *record_error = None;
+                }
+                Err(err) => *record_error = Some(err),
+            }
+        }
+    });
+    if let Some(ref recorder) = communicator.recorder {
+        ui.label(match recorder.error() {
+            Some(error) => error.to_string(),
+            None => format!(
+                "Recorded {} deltas to {}",
+                recorder.count(),
+                recorder.path()
+            ),
+        });
+    }
+    if let Some(error) = record_error {
+        ui.label(error.as_str());
+    }
+}
+
+/// The connection details for the diagnostics window.
+fn diagnostics_ui(ui: &mut egui::Ui, communicator: &SignalKCommunicator) {
+    let ago = |time: &Instant| format!("{:.1} s ago", time.elapsed().as_secs_f32());
+    egui::Grid::new("diagnostics")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Server");
+            ui.label(communicator.server());
+            ui.end_row();
+
+            ui.label("State");
+            ui.label(connection_status(communicator));
+            ui.end_row();
+
+            if let Some(discovery) = communicator.discovery() {
+                ui.label("Server version");
+                ui.label(format!(
+                    "{} {}",
+                    discovery.server.id, discovery.server.version
+                ));
+                ui.end_row();
+
+                let mut endpoints: Vec<_> = discovery.endpoints.iter().collect();
+                endpoints.sort_by_key(|(name, _)| name.as_str());
+                for (name, endpoint) in endpoints {
+                    ui.label(format!("Endpoints {} ({})", name, endpoint.version));
+                    ui.vertical(|ui| {
+                        for url in [
+                            &endpoint.signalk_http,
+                            &endpoint.signalk_ws,
+                            &endpoint.signalk_tcp,
+                        ]
+                        .into_iter()
+                        .flatten()
+                        {
+                            ui.label(url);
+                        }
+                    });
+                    ui.end_row();
+                }
+            }
+
+            ui.label("Delta messages");
+            ui.label(format!(
+                "{:.1}/s, {} in total",
+                communicator.message_rate(),
+                communicator.message_count()
+            ));
+            ui.end_row();
+
+            ui.label("Last message");
+            ui.label(
+                communicator
+                    .last_message_time()
+                    .as_ref()
+                    .map_or("Never".to_string(), ago),
+            );
+            ui.end_row();
+
+            ui.label("Subscribed paths");
+            ui.label(communicator.subscriptions().len().to_string());
+            ui.end_row();
+
+            ui.label("Last error");
+            ui.label(match communicator.last_error() {
+                Some((error, time)) => format!("{} ({})", error, ago(time)),
+                None => "None".to_string(),
+            });
+            ui.end_row();
+        });
+}
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct DisplayApplication {
+    /// Saved state from before versioning has no version, so it deserializes as 0.
+    #[serde(default)]
+    config_version: u32,
+    /// The server the display shows data from.
     server: String,
+    /// Servers that have worked, the most recent first.
+    recent_servers: Vec<String>,
+    /// The server address as it is being typed.
+    #[serde(skip)]
+    server_input: String,
+    /// Why the typed server address can't be used.
+    #[serde(skip)]
+    server_error: Option<String>,
+    connection: ConnectionSettings,
+    /// The connection settings as they are being edited, saved once a server streams with them.
+    #[serde(skip)]
+    connection_input: ConnectionSettings,
+    auth: Authentication,
+    /// The file deltas are recorded to, replay it with a `replay://` server address.
+    record_path: String,
+    #[serde(skip)]
+    record_error: Option<String>,
     view_config: bool,
+    digit_font: DigitFont,
+    theme: DisplayTheme,
+    /// Follow the SignalK sun mode, or the sun elevation, instead of `theme`.
+    auto_theme: bool,
     #[serde(skip)]
-    communicator: Option<SignalKCommunicator>,
+    applied_theme: Option<DisplayTheme>,
+    #[serde(skip)]
+    show_diagnostics: bool,
+    #[cfg(not(target_arch = "wasm32"))]
     #[serde(skip)]
-    layouts: Vec<crate::layouts::Layout>,
+    server_browser: ServerBrowser,
     #[serde(skip)]
+    communicator: Option<SignalKCommunicator>,
+    /// The server being switched to, it replaces `communicator` once it streams.
+    #[serde(skip)]
+    pending_communicator: Option<SignalKCommunicator>,
+    layouts: Vec<Layout>,
     current_layout: usize,
+    page_rotation: PageRotation,
+    rotation_interval_secs: f32,
+    /// The id given to the next page that is added.
+    next_layout_id: usize,
     #[serde(skip)]
-    last_layout_change: Instant,
+    new_layout_kind: LayoutKind,
     #[serde(skip)]
-    server_changed_tx: Option<Sender<String>>,
+    last_layout_change: Instant,
+    /// Horizontal distance of the drag in progress on the page.
     #[serde(skip)]
-    server_changed_rx: Option<Receiver<String>>,
+    swipe_distance: f32,
 }
 
 impl Default for DisplayApplication {
     fn default() -> Self {
         Self {
+            config_version: CONFIG_VERSION,
             server: "https://demo.signalk.org/signalk".to_owned(),
+            recent_servers: Vec::new(),
+            server_input: String::new(),
+            server_error: None,
+            connection: ConnectionSettings::default(),
+            connection_input: ConnectionSettings::default(),
+            auth: Authentication::default(),
+            record_path: "signalk-recording.jsonl".to_owned(),
+            record_error: None,
             view_config: false,
+            digit_font: DigitFont::default(),
+            theme: DisplayTheme::default(),
+            auto_theme: false,
+            applied_theme: None,
+            show_diagnostics: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            server_browser: ServerBrowser::default(),
             communicator: None,
+            pending_communicator: None,
             layouts: vec![
-                crate::layouts::Layout::DualValues(crate::layouts::DualValuesLayout::new(
-                    4,
-                    crate::datatypes::DataValues::SpeedOverGround(
-                        crate::datavalues::SpeedOverGround::default(),
-                    ),
-                    crate::datatypes::DataValues::CourseOverGround(
-                        crate::datavalues::CourseOverGround::default(),
-                    ),
-                )),
-                crate::layouts::Layout::SingleValue(crate::layouts::SingleValueLayout::new(
-                    0,
-                    crate::datatypes::DataValues::SpeedThroughWater(
-                        crate::datavalues::SpeedThroughWater::default(),
-                    ),
-                )),
-                crate::layouts::Layout::SingleValue(crate::layouts::SingleValueLayout::new(
-                    3,
-                    crate::datatypes::DataValues::WaterTemperature(
-                        crate::datavalues::WaterTemperature::default(),
-                    ),
+                Layout::new(LayoutKind::DualValues, 0),
+                Layout::new(LayoutKind::SingleValue, 1),
+                Layout::SingleValue(SingleValueLayout::new(
+                    2,
+                    DataValues::WaterTemperature(WaterTemperature::default()),
                 )),
             ],
+            next_layout_id: 3,
+            new_layout_kind: LayoutKind::SingleValue,
             current_layout: 0,
+            page_rotation: PageRotation::default(),
+            rotation_interval_secs: 3.0,
             last_layout_change: Instant::now(),
-            server_changed_tx: None,
-            server_changed_rx: None,
+            swipe_distance: 0.0,
         }
     }
 }
@@ -66,25 +380,188 @@ impl DisplayApplication {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let mut app = if let Some(storage) = cc.storage {
-            let restored_app: DisplayApplication =
-                eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
-            log::debug!("Restore object with server {}", restored_app.server);
-            restored_app
+            Self::restore(storage)
         } else {
             log::debug!("Creating new instance.");
             Self::default()
         };
+        cc.egui_ctx.set_fonts(font_definitions(app.digit_font));
         let mut communicator = SignalKCommunicator::default();
-        communicator.set_up_server_connections(app.server.to_string());
-        let (server_changed_tx, server_changed_rx): (Sender<String>, Receiver<String>) = channel();
-        app.server_changed_tx = Some(server_changed_tx);
-        app.server_changed_rx = Some(server_changed_rx);
+        communicator.set_token(app.auth.token(&app.server));
+        communicator.set_up_server_connections(app.server.to_string(), app.connection.clone());
+        app.server_input = app.server.clone();
+        app.connection_input = app.connection.clone();
 
         app.communicator = Some(communicator);
         app
     }
-    pub fn server_changed(&mut self) {
-        log::warn!("Server changed to {} IGNORED!!!", self.server);
+
+    /// Read the saved state. When it can't be read as a whole, the settings needed to reach
+    /// the server are still read one by one, so a page that can't be read doesn't lose them.
+    fn restore(storage: &dyn eframe::Storage) -> Self {
+        let mut app = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_else(|| {
+            let mut app = Self::default();
+            if storage.get_string(eframe::APP_KEY).is_some() {
+                log::warn!("Can't read the saved state, keeping only the server settings");
+                if let Some(SavedServer { server }) = eframe::get_value(storage, eframe::APP_KEY) {
+                    app.server = server;
+                }
+                if let Some(SavedRecentServers { recent_servers }) =
+                    eframe::get_value(storage, eframe::APP_KEY)
+                {
+                    app.recent_servers = recent_servers;
+                }
+                if let Some(SavedConnection { connection }) =
+                    eframe::get_value(storage, eframe::APP_KEY)
+                {
+                    app.connection = connection;
+                }
+                if let Some(SavedAuth { auth }) = eframe::get_value(storage, eframe::APP_KEY) {
+                    app.auth = auth;
+                }
+            }
+            app
+        });
+        log::debug!("Restore object with server {}", app.server);
+        app.migrate();
+        app
+    }
+
+    /// Bring restored state from an older version up to `CONFIG_VERSION`.
+    fn migrate(&mut self) {
+        if self.config_version < 1 {
+            // Version 0 only saved the server and view_config, the pages were hard coded.
+            log::info!("Migrating saved state from version {}", self.config_version);
+            self.layouts = Self::default().layouts;
+            self.current_layout = 0;
+        }
+        // Version 2 added page dwell times, value max ages and update periods, and more page
+        // kinds. They all have defaults, so version 1 pages read as they are.
+        if self.layouts.is_empty() {
+            self.layouts = Self::default().layouts;
+        }
+        if self.current_layout >= self.layouts.len() {
+            self.current_layout = 0;
+        }
+        if let Some(max_id) = self.layouts.iter().map(Layout::id).max() {
+            self.next_layout_id = self.next_layout_id.max(max_id + 1);
+        }
+        self.config_version = CONFIG_VERSION;
+    }
+
+    fn allocate_layout_id(next_layout_id: &mut usize) -> usize {
+        let id = *next_layout_id;
+        *next_layout_id += 1;
+        id
+    }
+
+    /// Change the page list, keeping the page being shown on the display. When that page is
+    /// deleted the one after it is shown, or the one before when it was the last.
+    fn apply_page_action(
+        layouts: &mut Vec<Layout>,
+        current_layout: &mut usize,
+        next_layout_id: &mut usize,
+        action: PageAction,
+    ) {
+        match action {
+            PageAction::Add(kind) => {
+                let id = Self::allocate_layout_id(next_layout_id);
+                layouts.push(Layout::new(kind, id));
+            }
+            PageAction::Delete(index) => {
+                layouts.remove(index);
+                if index < *current_layout {
+                    *current_layout -= 1;
+                }
+                *current_layout = (*current_layout).min(layouts.len().saturating_sub(1));
+            }
+            PageAction::Duplicate(index) => {
+                let mut layout = layouts[index].clone();
+                layout.set_id(Self::allocate_layout_id(next_layout_id));
+                layouts.insert(index + 1, layout);
+                if index < *current_layout {
+                    *current_layout += 1;
+                }
+            }
+            PageAction::MoveUp(index) => {
+                Self::swap_pages(layouts, current_layout, index, index - 1)
+            }
+            PageAction::MoveDown(index) => {
+                Self::swap_pages(layouts, current_layout, index, index + 1)
+            }
+        }
+    }
+
+    /// Swap two pages, keeping the page being shown on the display.
+    fn swap_pages(layouts: &mut [Layout], current_layout: &mut usize, a: usize, b: usize) {
+        layouts.swap(a, b);
+        if *current_layout == a {
+            *current_layout = b;
+        } else if *current_layout == b {
+            *current_layout = a;
+        }
+    }
+
+    /// Start connecting to a server typed by the user. The current server is kept, with its
+    /// data, until the new one streams.
+    fn connect_to(&mut self, input: &str) {
+        match normalize_server_url(input) {
+            Ok(url) => {
+                log::info!("Switching to server {}", url);
+                self.server_input = url.clone();
+                self.server_error = None;
+                let mut communicator = SignalKCommunicator::default();
+                communicator.set_token(self.auth.token(&url));
+                communicator.set_up_server_connections(url, self.connection_input.clone());
+                self.pending_communicator = Some(communicator);
+            }
+            Err(err) => {
+                log::warn!("Can't use server address {:?}: {}", input, err);
+                self.server_error = Some(err);
+            }
+        }
+    }
+
+    fn apply_server_action(&mut self, action: ServerAction) {
+        match action {
+            ServerAction::Connect(input) => self.connect_to(&input),
+            ServerAction::Reconnect => self.connect_to(&self.server.clone()),
+            ServerAction::Cancel => {
+                self.pending_communicator = None;
+                self.server_input = self.server.clone();
+                self.connection_input = self.connection.clone();
+            }
+        }
+    }
+
+    /// Use the server being switched to once it streams.
+    fn finish_server_switch(&mut self) {
+        let streaming = self
+            .pending_communicator
+            .as_ref()
+            .is_some_and(|pending| pending.state() == ConnectionState::Streaming);
+        if streaming {
+            if let Some(mut communicator) = self.pending_communicator.take() {
+                log::info!("Switched to server {}", communicator.server());
+                // Keep recording, from the new server.
+                communicator.recorder = self
+                    .communicator
+                    .as_mut()
+                    .and_then(|previous| previous.recorder.take());
+                self.server = communicator.server().to_string();
+                self.connection = communicator.connection().clone();
+                self.communicator = Some(communicator);
+            }
+        }
+        let streaming = self
+            .communicator
+            .as_ref()
+            .is_some_and(|communicator| communicator.state() == ConnectionState::Streaming);
+        if streaming && self.recent_servers.first() != Some(&self.server) {
+            self.recent_servers.retain(|recent| *recent != self.server);
+            self.recent_servers.insert(0, self.server.clone());
+            self.recent_servers.truncate(MAX_RECENT_SERVERS);
+        }
     }
 }
 
@@ -94,40 +571,96 @@ impl eframe::App for DisplayApplication {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         // log::debug!("TemplateApp::update() - Enter");
         ctx.request_repaint();
-        if let Some(ref mut sk_com) = self.communicator {
+        self.auth.handle_replies();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.server_browser.poll();
+        let mut subscriptions: Vec<PathSubscription> = self
+            .layouts
+            .iter()
+            .flat_map(Layout::subscriptions)
+            .collect();
+        if self.auto_theme {
+            subscriptions.extend(theme_subscriptions());
+        }
+        for sk_com in self
+            .communicator
+            .iter_mut()
+            .chain(self.pending_communicator.iter_mut())
+        {
+            sk_com.set_token(self.auth.token(sk_com.server()));
+            sk_com.set_subscriptions(subscriptions.clone());
+            sk_com
+                .history
+                .set_tracked(self.layouts.iter().flat_map(Layout::history_retention));
             // log::debug!("Handle sk_com.handle_data()");
             sk_com.handle_data(ctx);
         }
-        if let Some(ref mut server_changed_rx) = self.server_changed_rx {
-            // log::debug!("Server changed..");
-            if server_changed_rx.try_recv().is_ok() {
-                if let Some(ref mut communicator) = self.communicator {
-                    communicator.disconnect_server();
-                    communicator.set_up_server_connections(self.server.to_string());
-                } else {
-                    let mut communicator = SignalKCommunicator::default();
-                    communicator.set_up_server_connections(self.server.to_string());
-                    self.communicator = Some(communicator);
-                }
+        self.finish_server_switch();
+        if self.auto_theme {
+            if let Some(theme) = self.communicator.as_ref().and_then(automatic_theme) {
+                self.theme = theme;
             }
         }
+        if self.applied_theme != Some(self.theme) {
+            log::info!("Using the {} theme", self.theme.name());
+            self.theme.apply(ctx);
+            self.applied_theme = Some(self.theme);
+        }
         // log::debug!("Draw UI..");
 
         let Self {
             server,
+            recent_servers,
+            server_input,
+            server_error,
+            connection_input: connection,
+            auth,
+            record_path,
+            record_error,
             view_config,
+            digit_font,
+            theme,
+            auto_theme,
+            show_diagnostics,
+            #[cfg(not(target_arch = "wasm32"))]
+            server_browser,
             layouts,
             current_layout,
-            server_changed_tx,
+            page_rotation,
+            rotation_interval_secs,
             last_layout_change,
+            swipe_distance,
+            next_layout_id,
+            new_layout_kind,
             ..
         } = self;
 
-        if last_layout_change.elapsed() > Duration::from_secs(3) {
-            log::info!("Update current layout {}", *current_layout);
-            *last_layout_change = Instant::now();
-            *current_layout = (*current_layout + 1) % layouts.len();
-            log::info!("New current layout {}", *current_layout);
+        let dwell_secs = match page_rotation {
+            PageRotation::Manual => None,
+            PageRotation::Interval => Some(*rotation_interval_secs),
+            PageRotation::PerPage => Some(layouts[*current_layout].dwell_secs()),
+        };
+        if let Some(dwell_secs) = dwell_secs {
+            if last_layout_change.elapsed() > Duration::from_secs_f32(dwell_secs) {
+                log::info!("Update current layout {}", *current_layout);
+                *last_layout_change = Instant::now();
+                *current_layout = step_page(*current_layout, 1, layouts.len());
+                log::info!("New current layout {}", *current_layout);
+            }
+        }
+
+        let mut page_step: isize = 0;
+        if !ctx.wants_keyboard_input() {
+            ctx.input(|input| {
+                if input.key_pressed(egui::Key::ArrowRight)
+                    || input.key_pressed(egui::Key::PageDown)
+                {
+                    page_step += 1;
+                }
+                if input.key_pressed(egui::Key::ArrowLeft) || input.key_pressed(egui::Key::PageUp) {
+                    page_step -= 1;
+                }
+            });
         }
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
@@ -143,14 +676,46 @@ impl eframe::App for DisplayApplication {
                         ctx.send_viewport_cmd(ViewportCommand::Close)
                     }
                 });
-                if !frame.is_web() {
-                    ui.menu_button("View", |ui| {
-                        egui::gui_zoom::zoom_menu_buttons(ui);
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(auto_theme, "Automatic theme");
+                    ui.add_enabled_ui(!*auto_theme, |ui| {
+                        for display_theme in DisplayTheme::ALL {
+                            ui.radio_value(theme, display_theme, display_theme.name());
+                        }
                     });
+                    ui.checkbox(show_diagnostics, "Diagnostics");
+                    if !frame.is_web() {
+                        ui.separator();
+                        egui::gui_zoom::zoom_menu_buttons(ui);
+                    }
+                });
+                if let Some(ref mut communicator) = self.communicator {
+                    if status_badge(ui, communicator, &theme.palette()).clicked() {
+                        *show_diagnostics = !*show_diagnostics;
+                    }
+                    if communicator.is_stepped() && ui.button("Step").clicked() {
+                        communicator.step();
+                    }
                 }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("⏵").clicked() {
+                        page_step += 1;
+                    }
+                    ui.label(format!("{}/{}", *current_layout + 1, layouts.len()));
+                    if ui.button("⏴").clicked() {
+                        page_step -= 1;
+                    }
+                });
             });
         });
 
+        if let Some(ref communicator) = self.communicator {
+            egui::Window::new("Diagnostics")
+                .open(show_diagnostics)
+                .show(ctx, |ui| diagnostics_ui(ui, communicator));
+        }
+
+        let mut server_action = None;
         // Side panel for config? Maybe a different view?
         if *view_config {
             egui::SidePanel::left("side_panel").show(ctx, |ui| {
@@ -158,22 +723,182 @@ impl eframe::App for DisplayApplication {
 
                 ui.vertical(|ui| {
                     ui.label("Server Address: ");
-                    let response = ui.text_edit_singleline(server);
-                    if response.lost_focus() {
-                        if let Some(tx_channel) = server_changed_tx {
-                            if let Err(err) = tx_channel.send(server.to_string()) {
-                                log::error!("Can't send server changed message {:?}", err);
-                            };
+                    let response = ui.text_edit_singleline(server_input);
+                    if response.lost_focus() && server_input != server {
+                        server_action = Some(ServerAction::Connect(server_input.clone()));
+                    }
+                    if !recent_servers.is_empty() {
+                        egui::ComboBox::from_label("Recent servers")
+                            .selected_text("")
+                            .show_ui(ui, |ui| {
+                                for recent in recent_servers.iter() {
+                                    if ui.selectable_label(recent == server, recent).clicked() {
+                                        server_action = Some(ServerAction::Connect(recent.clone()));
+                                    }
+                                }
+                            });
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(picked) = server_browser_ui(ui, server_browser) {
+                        server_action = Some(ServerAction::Connect(picked));
+                    }
+                    let previous_mode = connection.mode;
+                    egui::ComboBox::from_label("Endpoints")
+                        .selected_text(connection.mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in EndpointMode::ALL {
+                                ui.selectable_value(&mut connection.mode, mode, mode.name());
+                            }
+                        });
+                    let mut reconnect = connection.mode != previous_mode;
+                    if connection.mode == EndpointMode::Direct {
+                        egui::Grid::new("endpoints").num_columns(2).show(ui, |ui| {
+                            ui.label("REST");
+                            reconnect |= ui
+                                .text_edit_singleline(&mut connection.http_endpoint)
+                                .lost_focus();
+                            ui.end_row();
+                            ui.label("Stream");
+                            reconnect |= ui
+                                .text_edit_singleline(&mut connection.ws_endpoint)
+                                .lost_focus();
+                            ui.end_row();
+                        });
+                    }
+                    if reconnect {
+                        server_action = Some(ServerAction::Reconnect);
+                    }
+                    if let Some(error) = server_error {
+                        ui.label(error.as_str());
+                    }
+                    if let Some(ref communicator) = self.communicator {
+                        ui.label(format!("Connection: {}", connection_status(communicator)));
+                    }
+                    if let Some(ref pending) = self.pending_communicator {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "Switching to {}: {}",
+                                pending.server(),
+                                connection_status(pending)
+                            ));
+                            if ui.button("Cancel").clicked() {
+                                server_action = Some(ServerAction::Cancel);
+                            }
+                        });
+                        if let Some((error, _)) = pending.last_error() {
+                            ui.label(error.as_str());
                         }
                     }
                 });
 
+                if !frame.is_web() {
+                    if let Some(ref mut communicator) = self.communicator {
+                        ui.add_space(6.);
+                        recording_ui(ui, record_path, record_error, communicator);
+                    }
+                }
+
+                ui.add_space(6.);
+
+                // Log in to the server being switched to, it may be why it doesn't connect.
+                let auth_server = self
+                    .pending_communicator
+                    .as_ref()
+                    .map_or(server.as_str(), |pending| pending.server());
+                auth.add_config(ui, auth_server);
+
+                ui.add_space(6.);
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Page rotation")
+                        .selected_text(page_rotation.name())
+                        .show_ui(ui, |ui| {
+                            for rotation in PageRotation::ALL {
+                                ui.selectable_value(page_rotation, rotation, rotation.name());
+                            }
+                        });
+                    if *page_rotation == PageRotation::Interval {
+                        ui.add(
+                            egui::DragValue::new(rotation_interval_secs)
+                                .range(1.0..=600.0)
+                                .suffix(" s"),
+                        );
+                    }
+                });
+
+                let previous_digit_font = *digit_font;
+                egui::ComboBox::from_label("Digit font")
+                    .selected_text(digit_font.name())
+                    .show_ui(ui, |ui| {
+                        for font in DigitFont::ALL {
+                            ui.selectable_value(digit_font, font, font.name());
+                        }
+                    });
+                if *digit_font != previous_digit_font {
+                    ctx.set_fonts(font_definitions(*digit_font));
+                }
+
                 ui.add_space(6.);
 
-                for layout in layouts.iter_mut() {
-                    ui.group(|ui| {
-                        layout.add_config(ui);
+                let mut page_action = None;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let page_count = layouts.len();
+                    for (index, layout) in layouts.iter_mut().enumerate() {
+                        ui.push_id(layout.id(), |ui| {
+                            ui.group(|ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Page {}", index + 1));
+                                    if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked()
+                                    {
+                                        page_action = Some(PageAction::MoveUp(index));
+                                    }
+                                    if ui
+                                        .add_enabled(index + 1 < page_count, egui::Button::new("⏷"))
+                                        .clicked()
+                                    {
+                                        page_action = Some(PageAction::MoveDown(index));
+                                    }
+                                    if ui.button("Duplicate").clicked() {
+                                        page_action = Some(PageAction::Duplicate(index));
+                                    }
+                                    if ui
+                                        .add_enabled(page_count > 1, egui::Button::new("Delete"))
+                                        .clicked()
+                                    {
+                                        page_action = Some(PageAction::Delete(index));
+                                    }
+                                });
+                                if *page_rotation == PageRotation::PerPage {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Show for");
+                                        ui.add(
+                                            egui::DragValue::new(layout.dwell_secs_mut())
+                                                .range(1.0..=600.0)
+                                                .suffix(" s"),
+                                        );
+                                    });
+                                }
+                                layout.add_config(ui);
+                            });
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("new_layout_kind")
+                            .selected_text(new_layout_kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in LayoutKind::ALL {
+                                    ui.selectable_value(new_layout_kind, kind, kind.name());
+                                }
+                            });
+                        if ui.button("Add page").clicked() {
+                            page_action = Some(PageAction::Add(*new_layout_kind));
+                        }
                     });
+                });
+
+                if let Some(action) = page_action {
+                    Self::apply_page_action(layouts, current_layout, next_layout_id, action);
                 }
 
                 ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
@@ -187,9 +912,38 @@ impl eframe::App for DisplayApplication {
             // ui.set_min_width(800.0);
             // ui.set_min_height(400.0);
             if let Some(ref comm) = self.communicator {
-                layouts[*current_layout].draw_ui(ui, comm);
+                let layout = &layouts[*current_layout];
+                let palette = theme.palette();
+                ui.push_id(layout.id(), |ui| layout.draw_ui(ui, comm, &palette));
+            }
+
+            // Swipe left or right on the page to change page.
+            const SWIPE_DISTANCE: f32 = 80.0;
+            let response = ui.interact(
+                ui.max_rect(),
+                ui.id().with("page_swipe"),
+                egui::Sense::drag(),
+            );
+            if response.dragged() {
+                *swipe_distance += response.drag_delta().x;
+            }
+            if response.drag_stopped() {
+                if *swipe_distance < -SWIPE_DISTANCE {
+                    page_step += 1;
+                } else if *swipe_distance > SWIPE_DISTANCE {
+                    page_step -= 1;
+                }
+                *swipe_distance = 0.0;
             }
         });
+
+        if page_step != 0 {
+            *current_layout = step_page(*current_layout, page_step, layouts.len());
+            *last_layout_change = Instant::now();
+        }
+        if let Some(action) = server_action {
+            self.apply_server_action(action);
+        }
         // log::debug!("TemplateApp::update() - Exit");
     }
 
@@ -198,3 +952,186 @@ impl eframe::App for DisplayApplication {
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn saved(state: &str) -> MemoryStorage {
+        let mut storage = MemoryStorage::default();
+        eframe::Storage::set_string(&mut storage, eframe::APP_KEY, state.to_string());
+        storage
+    }
+
+    fn layout_ids(app: &DisplayApplication) -> Vec<usize> {
+        app.layouts.iter().map(Layout::id).collect()
+    }
+
+    /// Apply `action` to pages with the ids 0 to 3, showing `current`. Gives the ids of the
+    /// pages after it and the id of the page shown.
+    fn page_action(current: usize, action: PageAction) -> (Vec<usize>, usize) {
+        let mut layouts = (0..4)
+            .map(|id| Layout::new(LayoutKind::SingleValue, id))
+            .collect::<Vec<_>>();
+        let mut current_layout = current;
+        let mut next_layout_id = 4;
+        DisplayApplication::apply_page_action(
+            &mut layouts,
+            &mut current_layout,
+            &mut next_layout_id,
+            action,
+        );
+        let ids = layouts.iter().map(Layout::id).collect::<Vec<_>>();
+        let shown = ids[current_layout];
+        (ids, shown)
+    }
+
+    #[test]
+    fn adds_pages() {
+        assert_eq!(
+            page_action(2, PageAction::Add(LayoutKind::Grid)),
+            (vec![0, 1, 2, 3, 4], 2)
+        );
+    }
+
+    #[test]
+    fn deletes_pages() {
+        assert_eq!(page_action(2, PageAction::Delete(0)), (vec![1, 2, 3], 2));
+        assert_eq!(page_action(2, PageAction::Delete(3)), (vec![0, 1, 2], 2));
+        assert_eq!(page_action(2, PageAction::Delete(2)), (vec![0, 1, 3], 3));
+        assert_eq!(page_action(3, PageAction::Delete(3)), (vec![0, 1, 2], 2));
+    }
+
+    #[test]
+    fn duplicates_pages() {
+        assert_eq!(
+            page_action(2, PageAction::Duplicate(0)),
+            (vec![0, 4, 1, 2, 3], 2)
+        );
+        assert_eq!(
+            page_action(2, PageAction::Duplicate(2)),
+            (vec![0, 1, 2, 4, 3], 2)
+        );
+        assert_eq!(
+            page_action(2, PageAction::Duplicate(3)),
+            (vec![0, 1, 2, 3, 4], 2)
+        );
+    }
+
+    #[test]
+    fn moves_pages() {
+        assert_eq!(page_action(2, PageAction::MoveUp(2)), (vec![0, 2, 1, 3], 2));
+        assert_eq!(page_action(2, PageAction::MoveUp(3)), (vec![0, 1, 3, 2], 2));
+        assert_eq!(
+            page_action(2, PageAction::MoveDown(0)),
+            (vec![1, 0, 2, 3], 2)
+        );
+        assert_eq!(
+            page_action(2, PageAction::MoveDown(1)),
+            (vec![0, 2, 1, 3], 2)
+        );
+    }
+
+    #[test]
+    fn restores_saved_state() {
+        let mut app = DisplayApplication {
+            server: "http://boat.local:3000/signalk".to_string(),
+            recent_servers: vec!["http://boat.local:3000/signalk".to_string()],
+            current_layout: 1,
+            page_rotation: PageRotation::PerPage,
+            ..DisplayApplication::default()
+        };
+        app.layouts.push(Layout::new(LayoutKind::Grid, 3));
+        app.next_layout_id = 4;
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, eframe::APP_KEY, &app);
+
+        let restored = DisplayApplication::restore(&storage);
+        assert_eq!(restored.config_version, CONFIG_VERSION);
+        assert_eq!(restored.server, app.server);
+        assert_eq!(restored.recent_servers, app.recent_servers);
+        assert_eq!(layout_ids(&restored), vec![0, 1, 2, 3]);
+        assert_eq!(restored.current_layout, 1);
+        assert_eq!(restored.page_rotation, PageRotation::PerPage);
+        assert_eq!(restored.next_layout_id, 4);
+    }
+
+    #[test]
+    fn migrates_version_0() {
+        let storage = saved(r#"(server: "http://boat.local:3000/signalk", view_config: true)"#);
+        let app = DisplayApplication::restore(&storage);
+        assert_eq!(app.config_version, CONFIG_VERSION);
+        assert_eq!(app.server, "http://boat.local:3000/signalk");
+        assert!(app.view_config);
+        assert_eq!(layout_ids(&app), vec![0, 1, 2]);
+        assert_eq!(app.current_layout, 0);
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let storage = saved(
+            r#"(
+                config_version: 1,
+                server: "http://boat.local:3000/signalk",
+                view_config: false,
+                layouts: [
+                    SingleValue((
+                        id: 4,
+                        value: AirTemperature((
+                            name: "Air Temperature",
+                            abbreviation: "AIR",
+                            display_unit: Fahrenheit,
+                        )),
+                    )),
+                ],
+                current_layout: 0,
+                next_layout_id: 2,
+            )"#,
+        );
+        let app = DisplayApplication::restore(&storage);
+        assert_eq!(app.config_version, CONFIG_VERSION);
+        assert_eq!(app.server, "http://boat.local:3000/signalk");
+        assert_eq!(layout_ids(&app), vec![4]);
+        assert_eq!(
+            app.layouts[0].dwell_secs(),
+            Layout::new(LayoutKind::Grid, 0).dwell_secs()
+        );
+        assert_eq!(app.next_layout_id, 5);
+    }
+
+    #[test]
+    fn keeps_server_settings_when_pages_cant_be_read() {
+        let storage = saved(
+            r#"(
+                config_version: 2,
+                server: "http://boat.local:3000/signalk",
+                recent_servers: ["http://boat.local:3000/signalk"],
+                connection: (mode: Direct),
+                auth: (tokens: {"http://boat.local:3000": "secret"}),
+                layouts: [NoSuchPage((id: 0))],
+            )"#,
+        );
+        let app = DisplayApplication::restore(&storage);
+        assert_eq!(app.server, "http://boat.local:3000/signalk");
+        assert_eq!(app.recent_servers, vec!["http://boat.local:3000/signalk"]);
+        assert_eq!(app.connection.mode, EndpointMode::Direct);
+        assert_eq!(app.auth.token(&app.server), Some("secret"));
+        assert_eq!(layout_ids(&app), vec![0, 1, 2]);
+    }
+}
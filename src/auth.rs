@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+use eframe::egui;
+use web_time::{Duration, Instant, SystemTime};
+
+use crate::communication::{parse_timestamp, url_origin};
+
+/// How often to ask the server if an access request has been approved.
+const ACCESS_REQUEST_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait for an access request to be approved, if the server doesn't say.
+const ACCESS_REQUEST_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Shown to the server administrator when approving an access request.
+const ACCESS_REQUEST_DESCRIPTION: &str = "SignalK MultiDisplay";
+
+/// Where getting a token from the server is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum AuthState {
+    #[default]
+    Idle,
+    RequestingAccess,
+    /// Waiting for the server administrator to approve the access request at `href`,
+    /// until it expires at `give_up_at`.
+    AwaitingApproval {
+        origin: String,
+        href: String,
+        poll_at: Instant,
+        give_up_at: Instant,
+    },
+    LoggingIn,
+    Failed(String),
+}
+
+impl AuthState {
+    pub fn description(&self) -> String {
+        match self {
+            AuthState::Idle => "Idle".to_string(),
+            AuthState::RequestingAccess => "Requesting access".to_string(),
+            AuthState::AwaitingApproval { .. } => "Waiting for approval on the server".to_string(),
+            AuthState::LoggingIn => "Logging in".to_string(),
+            AuthState::Failed(reason) => format!("Failed: {}", reason),
+        }
+    }
+}
+
+/// What the server answered to a request for a token.
+enum AuthReply {
+    /// The access request is waiting for approval, poll `href` to find out.
+    Pending {
+        href: String,
+        /// When the server drops the request, if it says.
+        expires: Option<SystemTime>,
+    },
+    Token(String),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessRequestReply {
+    state: String,
+    href: Option<String>,
+    expiration_time: Option<String>,
+    status_code: Option<u16>,
+    message: Option<String>,
+    access_request: Option<AccessRequestResult>,
+}
+
+#[derive(serde::Deserialize)]
+struct AccessRequestResult {
+    permission: String,
+    token: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct LoginReply {
+    token: String,
+}
+
+/// Tokens for secured servers, from an approved access request or a login.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Authentication {
+    /// Identifies this display in access requests, generated on first use.
+    client_id: String,
+    /// Tokens by the server origin they were issued by.
+    tokens: HashMap<String, String>,
+    username: String,
+    #[serde(skip)]
+    password: String,
+    #[serde(skip)]
+    state: AuthState,
+    #[serde(skip)]
+    reply_rx: Option<(String, Receiver<Result<AuthReply, String>>)>,
+}
+
+impl Authentication {
+    /// The token to use with `server`, if there is one.
+    pub fn token(&self, server: &str) -> Option<&str> {
+        self.tokens.get(url_origin(server)).map(String::as_str)
+    }
+
+    /// Ask the server for a device token. It has to be approved on the server before
+    /// it is handed out, `handle_replies` keeps polling until then.
+    pub fn request_access(&mut self, server: &str) {
+        if self.client_id.is_empty() {
+            self.client_id = generate_client_id();
+        }
+        let origin = url_origin(server).to_string();
+        let body = serde_json::json!({
+            "clientId": self.client_id,
+            "description": ACCESS_REQUEST_DESCRIPTION,
+        });
+        match ehttp::Request::json(format!("{}/signalk/v1/access/requests", origin), &body) {
+            Ok(request) => {
+                self.state = AuthState::RequestingAccess;
+                self.fetch(origin, request, parse_access_request_reply);
+            }
+            Err(err) => self.state = AuthState::Failed(err.to_string()),
+        }
+    }
+
+    /// Log in with the username and password to get a token.
+    pub fn login(&mut self, server: &str) {
+        let origin = url_origin(server).to_string();
+        let body = serde_json::json!({
+            "username": self.username,
+            "password": self.password,
+        });
+        match ehttp::Request::json(format!("{}/signalk/v1/auth/login", origin), &body) {
+            Ok(request) => {
+                self.state = AuthState::LoggingIn;
+                self.fetch(origin, request, |response| {
+                    if response.status == 401 {
+                        return Err("Wrong username or password".to_string());
+                    }
+                    check_status(response)?;
+                    response
+                        .json::<LoginReply>()
+                        .map(|reply| AuthReply::Token(reply.token))
+                        .map_err(|err| format!("Can't parse login reply: {}", err))
+                });
+            }
+            Err(err) => self.state = AuthState::Failed(err.to_string()),
+        }
+    }
+
+    /// Stop waiting for an access request or a login.
+    pub fn cancel(&mut self) {
+        self.reply_rx = None;
+        self.state = AuthState::Idle;
+    }
+
+    /// Forget the token for `server`.
+    pub fn forget_token(&mut self, server: &str) {
+        self.tokens.remove(url_origin(server));
+    }
+
+    /// Take care of answers from the server and poll pending access requests.
+    pub fn handle_replies(&mut self) {
+        if let AuthState::AwaitingApproval {
+            origin,
+            href,
+            poll_at,
+            give_up_at,
+        } = &self.state
+        {
+            if Instant::now() >= *give_up_at {
+                log::warn!("Access request to {} expired", origin);
+                self.reply_rx = None;
+                self.state = AuthState::Failed("Access request expired".to_string());
+                return;
+            }
+            if self.reply_rx.is_none() && Instant::now() >= *poll_at {
+                let url = if href.starts_with("http") {
+                    href.clone()
+                } else {
+                    format!("{}{}", origin, href)
+                };
+                self.fetch(
+                    origin.clone(),
+                    ehttp::Request::get(url),
+                    parse_access_request_reply,
+                );
+            }
+        }
+        let Some((origin, reply_rx)) = &self.reply_rx else {
+            return;
+        };
+        let reply = match reply_rx.try_recv() {
+            Ok(reply) => reply,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("Request dropped".to_string()),
+        };
+        let origin = origin.clone();
+        self.reply_rx = None;
+        match reply {
+            Ok(AuthReply::Pending { href, expires }) => {
+                let now = Instant::now();
+                // Keep the deadline of the first reply unless the server states its own.
+                let give_up_at = match (&self.state, expires) {
+                    (_, Some(expires)) => {
+                        now + expires
+                            .duration_since(SystemTime::now())
+                            .unwrap_or_default()
+                    }
+                    (AuthState::AwaitingApproval { give_up_at, .. }, None) => *give_up_at,
+                    (_, None) => now + ACCESS_REQUEST_TIMEOUT,
+                };
+                self.state = AuthState::AwaitingApproval {
+                    origin,
+                    href,
+                    poll_at: now + ACCESS_REQUEST_POLL_INTERVAL,
+                    give_up_at,
+                };
+            }
+            Ok(AuthReply::Token(token)) => {
+                log::info!("Got a token for {}", origin);
+                self.tokens.insert(origin, token);
+                self.password.clear();
+                self.state = AuthState::Idle;
+            }
+            Err(reason) => {
+                log::warn!("Authentication with {} failed: {}", origin, reason);
+                self.state = AuthState::Failed(reason);
+            }
+        }
+    }
+
+    fn fetch(
+        &mut self,
+        origin: String,
+        request: ehttp::Request,
+        parse: impl FnOnce(&ehttp::Response) -> Result<AuthReply, String> + Send + 'static,
+    ) {
+        let (reply_tx, reply_rx) = channel();
+        self.reply_rx = Some((origin, reply_rx));
+        ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
+            let reply = result.and_then(|response| parse(&response));
+            if let Err(err) = reply_tx.send(reply) {
+                log::error!("Can't send authentication reply back {:?}", err);
+            }
+        });
+    }
+
+    pub fn add_config(&mut self, ui: &mut egui::Ui, server: &str) {
+        ui.label("Authentication");
+        ui.horizontal(|ui| {
+            if self.token(server).is_some() {
+                ui.label("Token: saved");
+                if ui.button("Forget").clicked() {
+                    self.forget_token(server);
+                }
+            } else {
+                ui.label("Token: none");
+            }
+        });
+        let busy =
+            self.reply_rx.is_some() || matches!(self.state, AuthState::AwaitingApproval { .. });
+        ui.add_enabled_ui(!busy, |ui| {
+            if ui.button("Request access").clicked() {
+                self.request_access(server);
+            }
+            egui::Grid::new("login").num_columns(2).show(ui, |ui| {
+                ui.label("Username");
+                ui.text_edit_singleline(&mut self.username);
+                ui.end_row();
+                ui.label("Password");
+                ui.add(egui::TextEdit::singleline(&mut self.password).password(true));
+                ui.end_row();
+            });
+            if ui.button("Log in").clicked() {
+                self.login(server);
+            }
+        });
+        if self.state != AuthState::Idle {
+            ui.horizontal(|ui| {
+                ui.label(self.state.description());
+                if busy && ui.button("Cancel").clicked() {
+                    self.cancel();
+                }
+            });
+        }
+    }
+}
+
+/// An access request reply, or the state of a pending one when polled.
+fn parse_access_request_reply(response: &ehttp::Response) -> Result<AuthReply, String> {
+    check_status(response)?;
+    let reply = response
+        .json::<AccessRequestReply>()
+        .map_err(|err| format!("Can't parse access request reply: {}", err))?;
+    match reply.state.as_str() {
+        "PENDING" => reply
+            .href
+            .map(|href| AuthReply::Pending {
+                href,
+                expires: reply.expiration_time.as_deref().and_then(parse_timestamp),
+            })
+            .ok_or_else(|| "Pending access request has no href".to_string()),
+        "COMPLETED" => match reply.access_request {
+            Some(AccessRequestResult {
+                permission,
+                token: Some(token),
+            }) if permission == "APPROVED" => Ok(AuthReply::Token(token)),
+            Some(AccessRequestResult { permission, .. }) => {
+                Err(format!("Access request {}", permission.to_lowercase()))
+            }
+            None => Err(format!(
+                "Access request failed ({}): {}",
+                reply.status_code.unwrap_or_default(),
+                reply.message.unwrap_or_default()
+            )),
+        },
+        state => Err(format!("Unknown access request state {}", state)),
+    }
+}
+
+/// An error for responses that aren't a success, with the server's message if it sent one.
+pub(crate) fn check_status(response: &ehttp::Response) -> Result<(), String> {
+    if response.ok {
+        return Ok(());
+    }
+    let message = response.text().unwrap_or_default().trim();
+    match response.status {
+        401 | 403 => Err(format!(
+            "Not authorized ({}), log in or request access",
+            response.status
+        )),
+        status if message.is_empty() => Err(format!("{} {}", status, response.status_text)),
+        status => Err(format!("{} {}: {}", status, response.status_text, message)),
+    }
+}
+
+/// A random version 4 UUID, the form SignalK servers expect a client id in.
+fn generate_client_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web_time::UNIX_EPOCH;
+
+    fn response(status: u16, status_text: &str, body: &str) -> ehttp::Response {
+        ehttp::Response {
+            url: "http://boat.local:3000/signalk/v1/access/requests".to_string(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: status_text.to_string(),
+            headers: ehttp::Headers::default(),
+            bytes: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn reply(body: &str) -> Result<AuthReply, String> {
+        parse_access_request_reply(&response(202, "Accepted", body))
+    }
+
+    #[test]
+    fn parses_pending_access_requests() {
+        let pending = reply(
+            r#"{"state": "PENDING", "href": "/signalk/v1/requests/1",
+                "expirationTime": "2024-06-15T10:00:00.000Z"}"#,
+        );
+        let Ok(AuthReply::Pending { href, expires }) = pending else {
+            panic!("not pending");
+        };
+        assert_eq!(href, "/signalk/v1/requests/1");
+        assert_eq!(
+            expires,
+            Some(UNIX_EPOCH + Duration::from_secs(1_718_445_600))
+        );
+
+        let pending = reply(r#"{"state": "PENDING", "href": "/signalk/v1/requests/1"}"#);
+        assert!(matches!(
+            pending,
+            Ok(AuthReply::Pending { expires: None, .. })
+        ));
+    }
+
+    #[test]
+    fn parses_approved_access_requests() {
+        let approved = reply(
+            r#"{"state": "COMPLETED", "statusCode": 200,
+                "accessRequest": {"permission": "APPROVED", "token": "secret"}}"#,
+        );
+        assert!(matches!(approved, Ok(AuthReply::Token(token)) if token == "secret"));
+    }
+
+    #[test]
+    fn parses_denied_access_requests() {
+        let denied = reply(
+            r#"{"state": "COMPLETED", "statusCode": 200,
+                "accessRequest": {"permission": "DENIED"}}"#,
+        );
+        assert_eq!(denied.err().unwrap(), "Access request denied");
+
+        let failed = reply(
+            r#"{"state": "COMPLETED", "statusCode": 400, "message": "A device with this clientId is already pending"}"#,
+        );
+        assert_eq!(
+            failed.err().unwrap(),
+            "Access request failed (400): A device with this clientId is already pending"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_access_request_replies() {
+        assert!(reply("<html>Not SignalK</html>")
+            .err()
+            .unwrap()
+            .starts_with("Can't parse access request reply"));
+        assert!(reply(r#"{"href": "/signalk/v1/requests/1"}"#)
+            .err()
+            .unwrap()
+            .starts_with("Can't parse access request reply"));
+        assert_eq!(
+            reply(r#"{"state": "PENDING"}"#).err().unwrap(),
+            "Pending access request has no href"
+        );
+        assert_eq!(
+            reply(r#"{"state": "WAITING"}"#).err().unwrap(),
+            "Unknown access request state WAITING"
+        );
+    }
+
+    #[test]
+    fn checks_response_status() {
+        assert!(check_status(&response(200, "OK", "")).is_ok());
+        assert_eq!(
+            check_status(&response(401, "Unauthorized", "")),
+            Err("Not authorized (401), log in or request access".to_string())
+        );
+        assert_eq!(
+            check_status(&response(404, "Not Found", "")),
+            Err("404 Not Found".to_string())
+        );
+        assert_eq!(
+            check_status(&response(500, "Internal Server Error", " Out of memory\n")),
+            Err("500 Internal Server Error: Out of memory".to_string())
+        );
+        let error = parse_access_request_reply(&response(403, "Forbidden", ""));
+        assert_eq!(
+            error.err().unwrap(),
+            "Not authorized (403), log in or request access"
+        );
+    }
+
+    #[test]
+    fn generates_version_4_client_ids() {
+        let id = generate_client_id();
+        let uuid = uuid::Uuid::parse_str(&id).unwrap();
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_ne!(id, generate_client_id());
+    }
+}
@@ -0,0 +1,550 @@
+use ewebsock::{WsEvent, WsMessage};
+use signalk::{V1DeltaFormat, V1Subscribe, V1Subscription};
+use signalk_multidisplay::Recorder;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+mod stats;
+
+use stats::Stats;
+
+const USAGE: &str = "\
+Usage: wsdump [OPTIONS] [URL]
+
+Show what a SignalK server streams. URL is the stream endpoint, by default
+ws://127.0.0.1:3000/signalk/v1/stream
+
+Options:
+  --path PATH        Subscribe to PATH, may be given more than once, default everything
+  --period MS        How often the server sends the subscribed paths, default 1000,
+                     subscribes to every path if no --path is given
+  --context CONTEXT  The context to subscribe in, default vessels.self
+  --output MODE      raw, pretty, line, table or stats, default line
+  --format FORMAT    text or json, how the stats are reported, default text
+  --record FILE      Also record the deltas to FILE, replay it with a replay:// address
+  --duration SECS    Stop after SECS seconds
+  --count N          Stop after N deltas
+  --help             Show this help
+
+The stats mode counts the updates of every path and $source, and lists the
+values the display can show but that never arrived. The text report is
+redrawn every second, a json report is written when the dump stops.
+";
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:3000/signalk/v1/stream";
+
+/// Milliseconds between updates of the subscribed paths, unless `--period` says otherwise.
+const DEFAULT_PERIOD_MS: u64 = 1000;
+
+/// How long to wait for the next message when there is none.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How often the table and the stats are redrawn.
+const TABLE_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Like `println!`, but stops quietly when whatever reads the output has gone, like `head`.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if writeln!(std::io::stdout().lock(), $($arg)*).is_err() {
+            std::process::exit(0);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputMode {
+    /// The messages as they are received.
+    Raw,
+    /// The messages as indented JSON.
+    Pretty,
+    /// A line for every value in a delta.
+    Line,
+    /// The latest value of every path, redrawn every second.
+    Table,
+    /// How often every path is updated, and which values are missing.
+    Stats,
+}
+
+impl OutputMode {
+    fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "raw" => Ok(OutputMode::Raw),
+            "pretty" => Ok(OutputMode::Pretty),
+            "line" => Ok(OutputMode::Line),
+            "table" => Ok(OutputMode::Table),
+            "stats" => Ok(OutputMode::Stats),
+            _ => Err(format!("Unknown output mode {}", mode)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl ReportFormat {
+    fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Unknown report format {}", format)),
+        }
+    }
+}
+
+struct Args {
+    url: String,
+    paths: Vec<String>,
+    period: Option<u64>,
+    context: Option<String>,
+    output: OutputMode,
+    format: ReportFormat,
+    record: Option<String>,
+    duration: Option<Duration>,
+    count: Option<u64>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            url: DEFAULT_URL.to_string(),
+            paths: Vec::new(),
+            period: None,
+            context: None,
+            output: OutputMode::Line,
+            format: ReportFormat::Text,
+            record: None,
+            duration: None,
+            count: None,
+        };
+        while let Some(arg) = args.next() {
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => {
+                    (option.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            if !option.starts_with("--") {
+                parsed.url = arg;
+                continue;
+            }
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", option))?;
+            let number = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("{} isn't a number for {}", value, option))
+            };
+            match option.as_str() {
+                "--path" => parsed
+                    .paths
+                    .extend(value.split(',').map(|path| path.trim().to_string())),
+                "--period" => parsed.period = Some(number(&value)?),
+                "--context" => parsed.context = Some(value),
+                "--output" => parsed.output = OutputMode::parse(&value)?,
+                "--format" => parsed.format = ReportFormat::parse(&value)?,
+                "--record" => parsed.record = Some(value),
+                "--duration" => parsed.duration = Some(Duration::from_secs(number(&value)?)),
+                "--count" => parsed.count = Some(number(&value)?),
+                _ => return Err(format!("Unknown option {}", option)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// If the server is asked for something other than its default of everything.
+    fn subscribes(&self) -> bool {
+        !self.paths.is_empty() || self.context.is_some() || self.period.is_some()
+    }
+
+    fn stream_url(&self) -> String {
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        let subscribe = if self.subscribes() { "none" } else { "all" };
+        format!("{}{}subscribe={}", self.url, separator, subscribe)
+    }
+
+    fn subscribe_message(&self) -> Option<String> {
+        if !self.subscribes() {
+            return None;
+        }
+        let paths = if self.paths.is_empty() {
+            vec!["*".to_string()]
+        } else {
+            self.paths.clone()
+        };
+        let context = self.context.as_deref().unwrap_or("vessels.self");
+        let subscribe = paths
+            .into_iter()
+            .fold(
+                V1Subscribe::builder().context(context.to_string()),
+                |builder, path| {
+                    builder.subscribe(
+                        V1Subscription::builder()
+                            .path(path)
+                            .period(self.period.unwrap_or(DEFAULT_PERIOD_MS) as i64)
+                            .build(),
+                    )
+                },
+            )
+            .build();
+        match serde_json::to_string(&subscribe) {
+            Ok(message) => Some(message),
+            Err(err) => {
+                log::error!("Can't serialize subscription: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+/// The latest update of a path, for the table.
+struct Latest {
+    value: String,
+    source: String,
+    time: Instant,
+    count: u64,
+}
+
+/// Writes the messages to standard output in the chosen mode.
+struct Printer {
+    mode: OutputMode,
+    format: ReportFormat,
+    /// By context and path.
+    latest: BTreeMap<(String, String), Latest>,
+    stats: Stats,
+    last_draw: Option<Instant>,
+    changed: bool,
+}
+
+impl Printer {
+    fn new(mode: OutputMode, format: ReportFormat) -> Self {
+        Self {
+            mode,
+            format,
+            latest: BTreeMap::new(),
+            stats: Stats::default(),
+            last_draw: None,
+            changed: false,
+        }
+    }
+
+    fn message(&mut self, text: &str, delta: Option<&V1DeltaFormat>) {
+        match self.mode {
+            OutputMode::Raw => outln!("{}", text),
+            OutputMode::Pretty => match serde_json::from_str::<serde_json::Value>(text)
+                .and_then(|value| serde_json::to_string_pretty(&value))
+            {
+                Ok(pretty) => outln!("{}", pretty),
+                Err(_) => outln!("{}", text),
+            },
+            OutputMode::Line => {
+                if let Some(delta) = delta {
+                    let context = delta.context.as_deref().unwrap_or("-");
+                    for update in delta.updates.iter() {
+                        let timestamp = update.timestamp.as_deref().unwrap_or("-");
+                        let source = update.ref_source.as_deref().unwrap_or("-");
+                        for value in update.values.iter().flatten() {
+                            outln!(
+                                "{} {} {} = {} [{}]",
+                                timestamp,
+                                context,
+                                value.path,
+                                value.value,
+                                source
+                            );
+                        }
+                    }
+                }
+            }
+            OutputMode::Table => {
+                if let Some(delta) = delta {
+                    self.update_table(delta);
+                }
+            }
+            OutputMode::Stats => {
+                if let Some(delta) = delta {
+                    self.stats.record(delta);
+                    self.changed = true;
+                }
+            }
+        }
+    }
+
+    fn update_table(&mut self, delta: &V1DeltaFormat) {
+        let context = delta.context.clone().unwrap_or_else(|| "-".to_string());
+        let now = Instant::now();
+        for update in delta.updates.iter() {
+            let source = update.ref_source.clone().unwrap_or_else(|| "-".to_string());
+            for value in update.values.iter().flatten() {
+                let latest = self
+                    .latest
+                    .entry((context.clone(), value.path.clone()))
+                    .or_insert_with(|| Latest {
+                        value: String::new(),
+                        source: String::new(),
+                        time: now,
+                        count: 0,
+                    });
+                latest.value = value.value.to_string();
+                latest.source = source.clone();
+                latest.time = now;
+                latest.count += 1;
+            }
+        }
+        self.changed = true;
+    }
+
+    /// If the output is redrawn as deltas arrive.
+    fn redraws(&self) -> bool {
+        match self.mode {
+            OutputMode::Table => true,
+            OutputMode::Stats => self.format == ReportFormat::Text,
+            _ => false,
+        }
+    }
+
+    /// Redraw the table or the stats, if it is time to.
+    fn tick(&mut self, self_context: Option<&str>) {
+        let due = self
+            .last_draw
+            .map_or(true, |last| last.elapsed() >= TABLE_REDRAW_INTERVAL);
+        if self.redraws() && self.changed && due {
+            self.redraw(self_context);
+        }
+    }
+
+    fn finish(&mut self, self_context: Option<&str>) {
+        if self.redraws() {
+            self.redraw(self_context);
+        } else if self.mode == OutputMode::Stats {
+            outln!("{}", self.stats.report(self_context).to_json());
+        }
+    }
+
+    fn redraw(&mut self, self_context: Option<&str>) {
+        // Clear the terminal and draw from the top left.
+        if write!(std::io::stdout().lock(), "\x1b[2J\x1b[H").is_err() {
+            std::process::exit(0);
+        }
+        if self.mode == OutputMode::Stats {
+            outln!("{}", self.stats.report(self_context).to_text());
+        } else {
+            self.print_table();
+        }
+        self.last_draw = Some(Instant::now());
+        self.changed = false;
+    }
+
+    fn print_table(&self) {
+        let path_width = self
+            .latest
+            .keys()
+            .map(|(_, path)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let first_context = self.latest.keys().next().map(|(context, _)| context);
+        let several_contexts = self
+            .latest
+            .keys()
+            .any(|(context, _)| Some(context) != first_context);
+        outln!(
+            "{:<path_width$}  {:<24}  {:<20}  {:>6}  {:>7}",
+            "Path",
+            "Value",
+            "Source",
+            "Count",
+            "Age",
+            path_width = path_width
+        );
+        let mut last_context = None;
+        for ((context, path), latest) in self.latest.iter() {
+            if several_contexts && last_context != Some(context) {
+                outln!("{}", context);
+                last_context = Some(context);
+            }
+            outln!(
+                "{:<path_width$}  {:<24}  {:<20}  {:>6}  {:>5.1} s",
+                path,
+                latest.value,
+                latest.source,
+                latest.count,
+                latest.time.elapsed().as_secs_f32(),
+                path_width = path_width
+            );
+        }
+    }
+}
+
+fn main() {
+    init_logging();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    let args = match Args::parse(args.into_iter()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut recorder = match args.record.as_deref().map(Recorder::create).transpose() {
+        Ok(recorder) => recorder,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let url = args.stream_url();
+    log::info!("Connecting to {}", url);
+    let (mut sender, receiver) = match ewebsock::connect(&url, ewebsock::Options::default()) {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!("Can't connect to {}: {}", url, err);
+            std::process::exit(1);
+        }
+    };
+    let mut printer = Printer::new(args.output, args.format);
+    // The server's own vessel, from its hello message, for the recording and the stats.
+    let mut self_context: Option<String> = None;
+    let started = Instant::now();
+    let mut deltas: u64 = 0;
+    let mut failed = false;
+    loop {
+        if args
+            .duration
+            .is_some_and(|duration| started.elapsed() >= duration)
+            || args.count.is_some_and(|count| deltas >= count)
+        {
+            break;
+        }
+        let Some(event) = receiver.try_recv() else {
+            printer.tick(self_context.as_deref());
+            sleep(POLL_INTERVAL);
+            continue;
+        };
+        match event {
+            WsEvent::Opened => {
+                log::info!("Opened");
+                if let Some(message) = args.subscribe_message() {
+                    log::info!("Subscribing with {}", message);
+                    sender.send(WsMessage::Text(message));
+                }
+            }
+            WsEvent::Message(WsMessage::Text(text)) => {
+                match serde_json::from_str::<V1DeltaFormat>(&text) {
+                    Ok(delta) => {
+                        deltas += 1;
+                        // Without a hello, take the first delta to be about the server's vessel.
+                        if self_context.is_none() {
+                            self_context = delta.context.clone();
+                        }
+                        if let Some(ref mut recorder) = recorder {
+                            if let Some(ref self_context) = self_context {
+                                recorder.record(self_context, &delta);
+                            }
+                        }
+                        printer.message(&text, Some(&delta));
+                    }
+                    Err(_) => {
+                        let hello = serde_json::from_str::<serde_json::Value>(&text).ok();
+                        if let Some(hello_self) = hello
+                            .as_ref()
+                            .and_then(|hello| hello.get("self"))
+                            .and_then(|hello_self| hello_self.as_str())
+                        {
+                            log::info!("Server's own vessel is {}", hello_self);
+                            self_context = Some(hello_self.to_string());
+                        }
+                        printer.message(&text, None);
+                    }
+                }
+            }
+            WsEvent::Message(message) => log::debug!("Message: {:?}", message),
+            WsEvent::Error(error) => {
+                log::error!("Error: {:?}", error);
+                failed = true;
+                break;
+            }
+            WsEvent::Closed => {
+                log::info!("Closed");
+                break;
+            }
+        }
+    }
+    printer.finish(self_context.as_deref());
+    if let Some(recorder) = recorder {
+        match recorder.error() {
+            Some(error) => {
+                eprintln!("{}", error);
+                failed = true;
+            }
+            None => log::info!(
+                "Recorded {} deltas to {}",
+                recorder.count(),
+                recorder.path()
+            ),
+        }
+    }
+    log::info!("Received {} deltas in {:?}", deltas, started.elapsed());
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn init_logging() {
+    env_logger::init();
+}
+#[cfg(target_arch = "wasm32")]
+fn init_logging() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn streams_everything_by_default() {
+        let args = args(&[]);
+        assert_eq!(args.stream_url(), format!("{}?subscribe=all", DEFAULT_URL));
+        assert_eq!(args.subscribe_message(), None);
+    }
+
+    #[test]
+    fn subscribes_to_every_path_with_a_period() {
+        let args = args(&["--period", "200"]);
+        assert_eq!(args.stream_url(), format!("{}?subscribe=none", DEFAULT_URL));
+        let message: serde_json::Value =
+            serde_json::from_str(&args.subscribe_message().unwrap()).unwrap();
+        assert_eq!(message["context"], "vessels.self");
+        assert_eq!(message["subscribe"][0]["path"], "*");
+        assert_eq!(message["subscribe"][0]["period"], 200);
+    }
+
+    #[test]
+    fn subscribes_to_paths() {
+        let args = args(&[
+            "--path",
+            "navigation.speedOverGround,navigation.headingTrue",
+        ]);
+        let message: serde_json::Value =
+            serde_json::from_str(&args.subscribe_message().unwrap()).unwrap();
+        assert_eq!(
+            message["subscribe"][0]["path"],
+            "navigation.speedOverGround"
+        );
+        assert_eq!(message["subscribe"][1]["path"], "navigation.headingTrue");
+        assert_eq!(message["subscribe"][1]["period"], DEFAULT_PERIOD_MS);
+    }
+}
@@ -0,0 +1,290 @@
+use serde::Serialize;
+use serde_json::Value;
+use signalk::V1DeltaFormat;
+use signalk_multidisplay::DataValues;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Values longer than this are cut short in the text report.
+const VALUE_WIDTH: usize = 32;
+
+/// The updates of a path from one source.
+struct PathStats {
+    count: u64,
+    first: Instant,
+    last: Instant,
+    min_interval: Duration,
+    max_interval: Duration,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    last_value: Value,
+}
+
+impl PathStats {
+    fn new(now: Instant) -> Self {
+        Self {
+            count: 0,
+            first: now,
+            last: now,
+            min_interval: Duration::MAX,
+            max_interval: Duration::ZERO,
+            min_value: None,
+            max_value: None,
+            last_value: Value::Null,
+        }
+    }
+
+    fn add(&mut self, now: Instant, value: &Value) {
+        if self.count > 0 {
+            let interval = now - self.last;
+            self.min_interval = self.min_interval.min(interval);
+            self.max_interval = self.max_interval.max(interval);
+        }
+        self.count += 1;
+        self.last = now;
+        if let Some(number) = value.as_f64() {
+            self.min_value = Some(self.min_value.map_or(number, |min| min.min(number)));
+            self.max_value = Some(self.max_value.map_or(number, |max| max.max(number)));
+        }
+        self.last_value = value.clone();
+    }
+
+    fn interval(&self) -> Option<Spread> {
+        if self.count < 2 {
+            return None;
+        }
+        Some(Spread {
+            min: self.min_interval.as_secs_f64(),
+            mean: (self.last - self.first).as_secs_f64() / (self.count - 1) as f64,
+            max: self.max_interval.as_secs_f64(),
+        })
+    }
+}
+
+/// Counts the updates of every path, to see what a server provides and how often.
+pub struct Stats {
+    started: Instant,
+    deltas: u64,
+    /// By context, path and `$source`.
+    paths: BTreeMap<(String, String, String), PathStats>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            deltas: 0,
+            paths: BTreeMap::new(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn record(&mut self, delta: &V1DeltaFormat) {
+        let now = Instant::now();
+        self.deltas += 1;
+        let context = delta.context.clone().unwrap_or_else(|| "-".to_string());
+        for update in delta.updates.iter() {
+            let source = update.ref_source.clone().unwrap_or_else(|| "-".to_string());
+            for value in update.values.iter().flatten() {
+                self.paths
+                    .entry((context.clone(), value.path.clone(), source.clone()))
+                    .or_insert_with(|| PathStats::new(now))
+                    .add(now, &value.value);
+            }
+        }
+    }
+
+    /// Summarise what has arrived. `self_context` is the server's own vessel, the one the
+    /// display shows.
+    pub fn report(&self, self_context: Option<&str>) -> Report {
+        let is_self = |context: &str| {
+            context == "-" || context == "vessels.self" || Some(context) == self_context
+        };
+        let paths = self
+            .paths
+            .iter()
+            .map(|((context, path, source), stats)| PathReport {
+                context: context.clone(),
+                path: path.clone(),
+                source: source.clone(),
+                count: stats.count,
+                interval_secs: stats.interval(),
+                min_value: stats.min_value,
+                max_value: stats.max_value,
+                last_value: stats.last_value.clone(),
+            })
+            .collect();
+        let arrived = |expected: &str| {
+            let Some(expected) = expected.strip_prefix("self.") else {
+                return false;
+            };
+            self.paths
+                .iter()
+                .filter(|((context, _, _), _)| is_self(context))
+                .any(|((_, path, _), stats)| {
+                    // A value inside an object, like the altitude of a position, arrives with it.
+                    let rest = if path.is_empty() {
+                        Some(expected)
+                    } else if path == expected {
+                        return true;
+                    } else {
+                        expected
+                            .strip_prefix(path.as_str())
+                            .and_then(|rest| rest.strip_prefix('.'))
+                    };
+                    rest.is_some_and(|rest| {
+                        let pointer = format!("/{}", rest.replace('.', "/"));
+                        stats.last_value.pointer(&pointer).is_some()
+                    })
+                })
+        };
+        let missing = DataValues::all()
+            .into_iter()
+            .filter(|value| !value.paths().iter().any(|path| arrived(path)))
+            .map(|value| MissingValue {
+                abbreviation: value.abbreviation(),
+                name: value.name(),
+                paths: value.paths(),
+            })
+            .collect();
+        Report {
+            duration_secs: self.started.elapsed().as_secs_f64(),
+            deltas: self.deltas,
+            self_context: self_context.map(str::to_string),
+            paths,
+            missing,
+        }
+    }
+}
+
+/// Seconds between updates.
+#[derive(Serialize)]
+struct Spread {
+    min: f64,
+    mean: f64,
+    max: f64,
+}
+
+#[derive(Serialize)]
+struct PathReport {
+    context: String,
+    path: String,
+    source: String,
+    count: u64,
+    /// When there were at least two updates.
+    interval_secs: Option<Spread>,
+    /// The smallest and largest value, when the values are numbers.
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    last_value: Value,
+}
+
+/// A value the display can show, but none of whose paths arrived.
+#[derive(Serialize)]
+struct MissingValue {
+    abbreviation: String,
+    name: String,
+    paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    duration_secs: f64,
+    deltas: u64,
+    self_context: Option<String>,
+    paths: Vec<PathReport>,
+    missing: Vec<MissingValue>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|err| {
+            log::error!("Can't serialize report: {:?}", err);
+            String::new()
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!(
+            "{} deltas in {:.1} s",
+            self.deltas, self.duration_secs
+        )];
+        let path_width = self
+            .paths
+            .iter()
+            .map(|path| path.path.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let source_width = self
+            .paths
+            .iter()
+            .map(|path| path.source.len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<path_width$}  {:<source_width$}  {:>6}  {:<22}  Values",
+            "Path", "Source", "Count", "Interval min/mean/max"
+        ));
+        let mut last_context = None;
+        for path in self.paths.iter() {
+            if last_context != Some(&path.context) {
+                let own = if Some(&path.context) == self.self_context.as_ref() {
+                    " (self)"
+                } else {
+                    ""
+                };
+                lines.push(format!("{}{}", path.context, own));
+                last_context = Some(&path.context);
+            }
+            let interval = path
+                .interval_secs
+                .as_ref()
+                .map_or("-".to_string(), |spread| {
+                    format!("{:.2}/{:.2}/{:.2} s", spread.min, spread.mean, spread.max)
+                });
+            let values = match (path.min_value, path.max_value) {
+                (Some(min), Some(max)) => format!("{:.3} .. {:.3}", min, max),
+                _ => shorten(&path.last_value.to_string()),
+            };
+            lines.push(format!(
+                "{:<path_width$}  {:<source_width$}  {:>6}  {:<22}  {}",
+                path.path, path.source, path.count, interval, values
+            ));
+        }
+        lines.push(String::new());
+        if self.missing.is_empty() {
+            lines.push("Every value the display can show has arrived".to_string());
+        } else {
+            lines.push("Values the display can show, but none of their paths arrived:".to_string());
+            let name_width = self
+                .missing
+                .iter()
+                .map(|missing| missing.name.len())
+                .max()
+                .unwrap_or(0);
+            for missing in self.missing.iter() {
+                lines.push(format!(
+                    "  {:<5} {:<name_width$}  {}",
+                    missing.abbreviation,
+                    missing.name,
+                    missing.paths.join(", ")
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= VALUE_WIDTH {
+        return text.to_string();
+    }
+    let mut short = text.chars().take(VALUE_WIDTH - 3).collect::<String>();
+    short.push_str("...");
+    short
+}
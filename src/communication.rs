@@ -1,68 +1,380 @@
 use egui::Context;
 use ewebsock::{WsEvent, WsMessage, WsReceiver, WsSender};
 use signalk::{
-    SignalKGetError, Storage, V1DeltaFormat, V1Discovery, V1FullFormat, V1Subscribe, V1Subscription,
+    SignalKGetError, Storage, V1DeltaFormat, V1Discovery, V1FullFormat, V1Subscribe,
+    V1Subscription, V1Unsubscribe, V1Unsubscription,
 };
+use std::collections::{HashMap, VecDeque};
 use std::str::from_utf8;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::auth::check_status;
+use crate::datasource::{is_data_source, open_data_source, DeltaSource};
+use crate::history::History;
+use crate::recording::Recorder;
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum WebSocketError {
     ServerNotCreated,
     NoSuchPath,
     WrongDataType,
     ValueNotSet,
     TBD,
+    /// The value is older than the max age for it.
+    Stale,
+}
+
+/// Where the REST and stream endpoints to connect to come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum EndpointMode {
+    /// The endpoints the server advertises in its discovery document.
+    #[default]
+    Advertised,
+    /// The advertised endpoints, with the host of the server address instead, for
+    /// servers behind a proxy that advertise internal addresses.
+    RewriteHost,
+    /// The endpoints in the settings, without asking the server.
+    Direct,
+}
+
+impl EndpointMode {
+    pub const ALL: [EndpointMode; 3] = [
+        EndpointMode::Advertised,
+        EndpointMode::RewriteHost,
+        EndpointMode::Direct,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EndpointMode::Advertised => "Advertised",
+            EndpointMode::RewriteHost => "Advertised, on the server host",
+            EndpointMode::Direct => "Direct",
+        }
+    }
+}
+
+/// How to find the endpoints of the server.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    pub mode: EndpointMode,
+    /// The REST API for `EndpointMode::Direct`, like `http://host:3000/signalk/v1/api/`.
+    pub http_endpoint: String,
+    /// The stream for `EndpointMode::Direct`, like `ws://host:3000/signalk/v1/stream`.
+    pub ws_endpoint: String,
+}
+
+/// The scheme, host and port of a URL.
+pub(crate) fn url_origin(url: &str) -> &str {
+    let authority_start = url.find("://").map_or(0, |index| index + 3);
+    match url[authority_start..].find('/') {
+        Some(index) => &url[..authority_start + index],
+        None => url,
+    }
+}
+
+/// Check a server address typed by the user and put it in the form discovery needs, like
+/// `http://host:3000/signalk`. The scheme defaults to http, websocket schemes are swapped
+/// for their http ones and a missing path becomes `/signalk`. Data source addresses, like
+/// `tcp://host:10110`, are used as they are.
+pub(crate) fn normalize_server_url(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("The server address is empty".to_string());
+    }
+    if is_data_source(input) {
+        return Ok(input.to_string());
+    }
+    let (scheme, rest) = match input.split_once("://") {
+        Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+        None => ("http".to_string(), input),
+    };
+    let scheme = match scheme.as_str() {
+        "http" | "ws" => "http",
+        "https" | "wss" => "https",
+        _ => {
+            return Err(format!(
+                "Unsupported scheme {}://, use http or https",
+                scheme
+            ))
+        }
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    if authority.is_empty() {
+        return Err("The server address has no host".to_string());
+    }
+    if authority.contains(char::is_whitespace) || authority.contains('@') {
+        return Err(format!("{} isn't a valid host", authority));
+    }
+    // The port is what follows the last colon, unless that is inside an IPv6 address.
+    if let Some((_, port)) = authority
+        .rsplit_once(':')
+        .filter(|(_, port)| !port.contains(']'))
+    {
+        if port.parse::<u16>().is_err() {
+            return Err(format!("{} isn't a valid port", port));
+        }
+    }
+    let path = path.trim_end_matches('/');
+    let path = if path.is_empty() { "/signalk" } else { path };
+    Ok(format!("{}://{}{}", scheme, authority, path))
+}
+
+/// Move an advertised endpoint to the host and port of `server`, keeping its path and
+/// using TLS if the server address does.
+fn rewrite_host(endpoint: &str, server: &str) -> String {
+    let server_origin = url_origin(server);
+    let secure = server_origin.starts_with("https://") || server_origin.starts_with("wss://");
+    let host = server_origin
+        .split_once("://")
+        .map_or(server_origin, |(_, host)| host);
+    let (scheme, rest) = endpoint.split_once("://").unwrap_or(("http", endpoint));
+    let path = rest.find('/').map_or("", |index| &rest[index..]);
+    let scheme = match (scheme, secure) {
+        ("ws" | "wss", true) => "wss",
+        ("ws" | "wss", false) => "ws",
+        (_, true) => "https",
+        (_, false) => "http",
+    };
+    format!("{}://{}{}", scheme, host, path)
+}
+
+/// Where the communicator is in connecting to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Discovering,
+    FetchingFullModel,
+    /// The full model has arrived, waiting for the stream to open.
+    ModelLoaded,
+    /// Waiting for the first data from a data source that isn't a SignalK server.
+    Connecting,
+    Streaming,
+    /// The stream was lost, waiting until `retry_at` before running discovery again.
+    Reconnecting {
+        retry_at: Instant,
+    },
+    /// The server hasn't streamed since it was set, waiting until `retry_at` to try again.
+    Error {
+        retry_at: Instant,
+    },
+}
+
+impl ConnectionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "Disconnected",
+            ConnectionState::Discovering => "Discovering",
+            ConnectionState::FetchingFullModel => "Fetching full model",
+            ConnectionState::ModelLoaded => "Model loaded",
+            ConnectionState::Connecting => "Connecting",
+            ConnectionState::Streaming => "Streaming",
+            ConnectionState::Reconnecting { .. } => "Reconnecting",
+            ConnectionState::Error { .. } => "Error",
+        }
+    }
+
+    /// When the next connection attempt is made, if waiting to retry.
+    pub fn retry_at(&self) -> Option<Instant> {
+        match self {
+            ConnectionState::Reconnecting { retry_at } | ConnectionState::Error { retry_at } => {
+                Some(*retry_at)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Delay before the first reconnection attempt, doubled for each failed attempt.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// The time over which the message rate is averaged.
+const MESSAGE_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Counts of the delta messages received, for the diagnostics window.
+#[derive(Default)]
+struct MessageStats {
+    total: u64,
+    last: Option<Instant>,
+    /// Arrival times within the last `MESSAGE_RATE_WINDOW`.
+    recent: VecDeque<Instant>,
+}
+
+impl MessageStats {
+    fn record(&mut self, now: Instant) {
+        self.total += 1;
+        self.last = Some(now);
+        self.recent.push_back(now);
+        while let Some(first) = self.recent.front() {
+            if now.duration_since(*first) > MESSAGE_RATE_WINDOW {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Messages per second over the last `MESSAGE_RATE_WINDOW`.
+    fn rate(&self) -> f32 {
+        let now = Instant::now();
+        let count = self
+            .recent
+            .iter()
+            .filter(|time| now.duration_since(**time) <= MESSAGE_RATE_WINDOW)
+            .count();
+        count as f32 / MESSAGE_RATE_WINDOW.as_secs_f32()
+    }
+}
+
+/// How the server decides when to send an update for a subscribed path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscriptionPolicy {
+    /// Send every change, but not more often than the min period.
+    Instant,
+    /// Send changes as they happen, and the last value if nothing changed for a period.
+    Ideal,
+    /// Send the last value once every period.
+    Fixed,
+}
+
+impl SubscriptionPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SubscriptionPolicy::Instant => "instant",
+            SubscriptionPolicy::Ideal => "ideal",
+            SubscriptionPolicy::Fixed => "fixed",
+        }
+    }
+}
+
+/// A path of our own vessel the pages need updates for, and how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathSubscription {
+    /// The path as used by the data values, e.g. `self.navigation.speedThroughWater`.
+    pub path: String,
+    pub period: Duration,
+    pub min_period: Duration,
+    pub policy: SubscriptionPolicy,
+}
+
+impl PathSubscription {
+    /// Updates about every `period`. Values wanted at least once a second are sent as they
+    /// change, but not more often than the period.
+    pub fn new(path: String, period: Duration) -> Self {
+        if period <= Duration::from_secs(1) {
+            Self {
+                path,
+                period,
+                min_period: period,
+                policy: SubscriptionPolicy::Instant,
+            }
+        } else {
+            Self {
+                path,
+                period,
+                min_period: period / 5,
+                policy: SubscriptionPolicy::Ideal,
+            }
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SubscriptionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn to_v1_subscription(&self) -> V1Subscription {
+        // Subscription paths are relative to the vessel context.
+        let path = self.path.strip_prefix("self.").unwrap_or(&self.path);
+        V1Subscription::builder()
+            .path(path.to_string())
+            .period(self.period.as_millis() as i64)
+            .min_period(self.min_period.as_millis() as i64)
+            .policy(self.policy.name().to_string())
+            .build()
+    }
+}
+
+/// Sort the subscriptions by path and keep only the most frequent one for each path.
+fn merge_subscriptions(mut subscriptions: Vec<PathSubscription>) -> Vec<PathSubscription> {
+    subscriptions.sort_by(|a, b| a.path.cmp(&b.path).then(a.period.cmp(&b.period)));
+    subscriptions.dedup_by(|later, first| later.path == first.path);
+    subscriptions
 }
 
 pub struct WebsocketHandler {
     ws_receiver: WsReceiver,
     ws_sender: WsSender,
+    opened: bool,
+    /// What the server was last asked to send on this connection.
+    subscribed: Option<Vec<PathSubscription>>,
 }
 
 impl WebsocketHandler {
-    fn recv_signalk_delta_messages(&mut self, storage: &mut Storage) {
+    /// Handle the next websocket event, if any. Returns if a message was received, or an
+    /// error when the stream is lost.
+    fn recv_signalk_delta_messages(
+        &mut self,
+        storage: &mut Storage,
+        self_values: &mut HashMap<String, ReceivedValue>,
+        history: &mut History,
+        recorder: Option<&mut Recorder>,
+    ) -> Result<bool, String> {
         log::debug!("recv_signalk_delta_messages()",);
         let got_message = self.ws_receiver.try_recv();
         log::debug!("got_message: {:?}", got_message);
         if let Some(ws_event) = got_message {
             log::debug!("recv_signalk_delta_messages(): {:?}", ws_event);
-            Self::handle_ws_event(self, storage, ws_event);
+            Self::handle_ws_event(self, storage, self_values, history, recorder, ws_event)
+        } else {
+            Ok(false)
         }
     }
 
-    fn handle_ws_event(&mut self, storage: &mut Storage, ws_event: WsEvent) {
+    fn handle_ws_event(
+        &mut self,
+        storage: &mut Storage,
+        self_values: &mut HashMap<String, ReceivedValue>,
+        history: &mut History,
+        recorder: Option<&mut Recorder>,
+        ws_event: WsEvent,
+    ) -> Result<bool, String> {
         match ws_event {
             WsEvent::Opened => {
                 log::info!("WebSocket delta opened.");
-                let subscribe = V1Subscribe::builder()
-                    .context("self".to_string())
-                    .subscribe(
-                        V1Subscription::builder()
-                            .path("*".to_string())
-                            .period(5000)
-                            .build(),
-                    )
-                    .build();
-                if let Ok(s) = serde_json::to_string(&subscribe) {
-                    let message = WsMessage::Text(s.into());
-                    self.ws_sender.send(message);
-                }
+                self.opened = true;
             }
             WsEvent::Message(ws_message) => {
                 log::debug!("WebSocket message.");
-                Self::handle_ws_message(storage, ws_message);
+                Self::handle_ws_message(storage, self_values, history, recorder, ws_message);
+                return Ok(true);
             }
             WsEvent::Error(ws_error) => {
-                log::error!("Websocket error: {:?}", ws_error)
+                log::error!("Websocket error: {:?}", ws_error);
+                return Err(format!("Websocket error: {}", ws_error));
             }
             WsEvent::Closed => {
                 log::info!("WebSocket delta closed.");
+                return Err("Websocket closed".to_string());
             }
         }
+        Ok(false)
     }
 
-    fn handle_ws_message(storage: &mut Storage, ws_message: WsMessage) {
+    fn handle_ws_message(
+        storage: &mut Storage,
+        self_values: &mut HashMap<String, ReceivedValue>,
+        history: &mut History,
+        recorder: Option<&mut Recorder>,
+        ws_message: WsMessage,
+    ) {
         match ws_message {
             WsMessage::Binary(_) => {
                 log::debug!("Binary ws message.");
@@ -73,7 +385,10 @@ impl WebsocketHandler {
                     serde_json::from_str(data.as_str());
                 if let Ok(sk_delta) = maybe_sk_delta {
                     log::debug!("New sk delta: {:?}", sk_delta);
-                    storage.update(&sk_delta);
+                    if let Some(recorder) = recorder {
+                        recorder.record(&storage.data().self_, &sk_delta);
+                    }
+                    apply_delta(storage, self_values, history, &sk_delta, true);
                 }
             }
             WsMessage::Unknown(_) => {
@@ -87,55 +402,425 @@ impl WebsocketHandler {
             }
         }
     }
+
+    /// Replace what the server sends on this connection, if it differs from `subscriptions`.
+    fn update_subscriptions(&mut self, subscriptions: &[PathSubscription]) {
+        if !self.opened || self.subscribed.as_deref() == Some(subscriptions) {
+            return;
+        }
+        log::info!("Subscribing to {} paths", subscriptions.len());
+        let unsubscribe = V1Unsubscribe::builder()
+            .context("*".to_string())
+            .unsubscribe(V1Unsubscription::builder().path("*".to_string()).build())
+            .build();
+        let subscribe = subscriptions
+            .iter()
+            .fold(
+                V1Subscribe::builder().context("vessels.self".to_string()),
+                |builder, subscription| builder.subscribe(subscription.to_v1_subscription()),
+            )
+            .build();
+        for message in [
+            serde_json::to_string(&unsubscribe),
+            serde_json::to_string(&subscribe),
+        ] {
+            match message {
+                Ok(text) => self.ws_sender.send(WsMessage::Text(text)),
+                Err(err) => log::error!("Can't serialize subscription: {:?}", err),
+            }
+        }
+        self.subscribed = Some(subscriptions.to_vec());
+    }
+}
+
+/// Update the storage with a delta. Keep the raw values of our own vessel too, for paths
+/// that `Storage` doesn't model, and record the numeric ones in the history. With
+/// `use_timestamps` the age of the values counts from the timestamps in the delta.
+fn apply_delta(
+    storage: &mut Storage,
+    self_values: &mut HashMap<String, ReceivedValue>,
+    history: &mut History,
+    delta: &V1DeltaFormat,
+    use_timestamps: bool,
+) {
+    storage.update(delta);
+    if !is_self_context(storage, &delta.context) {
+        return;
+    }
+    let now = Instant::now();
+    for update in delta.updates.iter() {
+        let timestamp = update
+            .timestamp
+            .as_deref()
+            .filter(|_| use_timestamps)
+            .and_then(parse_timestamp);
+        for value in update.values.iter().flatten() {
+            let path = format!("self.{}", value.path);
+            if let Some(number) = value.value.as_f64() {
+                history.record(&path, now, number);
+            }
+            self_values.insert(
+                path,
+                ReceivedValue {
+                    value: value.value.clone(),
+                    time: now,
+                    timestamp,
+                },
+            );
+        }
+    }
+}
+
+/// A value from a delta and when it was received.
+struct ReceivedValue {
+    value: serde_json::Value,
+    time: Instant,
+    /// When the source says the value was measured, if the delta has a timestamp.
+    timestamp: Option<SystemTime>,
+}
+
+/// Parse a SignalK timestamp, an RFC 3339 time like `2024-06-01T12:30:00.123Z`.
+pub(crate) fn parse_timestamp(text: &str) -> Option<SystemTime> {
+    let (date, time) = text.split_once(['T', ' '])?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+    let (time, offset_secs) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let index = time.rfind(['+', '-'])?;
+            let (time, offset) = time.split_at(index);
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+            (time, sign * offset)
+        }
+    };
+    let mut time_parts = time.splitn(3, ':');
+    let hours = time_parts.next()?.parse::<i64>().ok()?;
+    let minutes = time_parts.next()?.parse::<i64>().ok()?;
+    let seconds = time_parts.next()?.parse::<f64>().ok()?;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hours)
+        || !(0..60).contains(&minutes)
+        || !(0.0..61.0).contains(&seconds)
+    {
+        return None;
+    }
+    let whole_secs =
+        days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 - offset_secs;
+    let secs = whole_secs as f64 + seconds;
+    if secs < 0.0 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_secs_f64(secs))
+}
+
+/// Escape everything but the unreserved characters of RFC 3986, for a query parameter.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    // Years start in March, so the leap day is the last day of the year.
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Is a delta with this context about our own vessel.
+fn is_self_context(storage: &Storage, context: &Option<String>) -> bool {
+    match context {
+        None => true,
+        Some(context) => {
+            let self_id = &storage.data().self_;
+            context == "self"
+                || context == self_id
+                || context.strip_prefix("vessels.") == Some(self_id.as_str())
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct SignalKCommunicator {
     pub(crate) signalk_data: Option<Storage>,
+    self_values: HashMap<String, ReceivedValue>,
+    pub(crate) history: History,
+    /// The paths the pages need, merged and sorted by path.
+    subscriptions: Vec<PathSubscription>,
+    server: String,
+    connection: ConnectionSettings,
+    /// Sent with the requests to the server, if it is secured.
+    token: Option<String>,
+    state: ConnectionState,
+    /// When the full model was received, the age of values no delta has updated yet.
+    full_model_time: Option<Instant>,
+    /// Our own vessel in the full model, for the timestamps of its values.
+    full_model_self: Option<serde_json::Value>,
+    /// Failed connection attempts since the stream last worked.
+    reconnect_attempts: u32,
+    /// If the stream has worked since the server was set.
+    has_streamed: bool,
+    last_error: Option<(String, Instant)>,
+    message_stats: MessageStats,
     signalk_discovery: Option<V1Discovery>,
-    discovery_rx: Option<Receiver<V1Discovery>>,
-    full_rx: Option<Receiver<V1FullFormat>>,
+    discovery_rx: Option<Receiver<Result<V1Discovery, String>>>,
+    full_rx: Option<Receiver<Result<V1FullFormat, String>>>,
     ws_handler: Option<WebsocketHandler>,
+    /// Where the deltas come from when the server address is a data source.
+    source: Option<Box<dyn DeltaSource>>,
+    /// Writes the deltas received to a file, while recording.
+    pub(crate) recorder: Option<Recorder>,
 }
 
 impl SignalKCommunicator {
-    pub(crate) fn disconnect_server(&mut self) {
-        self.signalk_data = None;
-        self.signalk_discovery = None;
-        self.discovery_rx = None;
-        self.full_rx = None;
-        self.ws_handler = None;
+    pub(crate) fn set_up_server_connections(
+        &mut self,
+        server: String,
+        connection: ConnectionSettings,
+    ) {
+        log::info!("set_up_server_connections({}, {:?})", server, connection);
+        self.server = server;
+        self.connection = connection;
+        self.connect();
+    }
+
+    pub(crate) fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Use a new token, reconnecting with it if the server is already set up.
+    pub(crate) fn set_token(&mut self, token: Option<&str>) {
+        if self.token.as_deref() == token {
+            return;
+        }
+        self.token = token.map(str::to_string);
+        if self.state != ConnectionState::Disconnected {
+            log::info!("Token changed, reconnecting to {}", self.server);
+            self.discovery_rx = None;
+            self.full_rx = None;
+            self.ws_handler = None;
+            self.reconnect_attempts = 0;
+            self.connect();
+        }
     }
-    pub(crate) fn set_up_server_connections(&mut self, server: String) {
-        log::info!("set_up_server_connections({})", server);
-        let request = ehttp::Request::get(server);
-        let (signalk_tx, signalk_rx): (Sender<V1Discovery>, Receiver<V1Discovery>) = channel();
+
+    /// Add the token to a request, if there is one.
+    fn authorize(&self, request: &mut ehttp::Request) {
+        if let Some(token) = &self.token {
+            request
+                .headers
+                .insert("Authorization", format!("Bearer {}", token));
+        }
+    }
+
+    pub(crate) fn reconnect_attempts(&self) -> u32 {
+        self.reconnect_attempts
+    }
+
+    /// Set the paths to get updates for, the server is asked to change once the stream is open.
+    pub(crate) fn set_subscriptions(&mut self, subscriptions: Vec<PathSubscription>) {
+        let subscriptions = merge_subscriptions(subscriptions);
+        if subscriptions != self.subscriptions {
+            log::debug!("Subscriptions changed to {:?}", subscriptions);
+            self.subscriptions = subscriptions;
+        }
+    }
+
+    pub(crate) fn subscriptions(&self) -> &[PathSubscription] {
+        &self.subscriptions
+    }
+
+    pub(crate) fn server(&self) -> &str {
+        &self.server
+    }
+
+    pub(crate) fn connection(&self) -> &ConnectionSettings {
+        &self.connection
+    }
+
+    pub(crate) fn discovery(&self) -> Option<&V1Discovery> {
+        self.signalk_discovery.as_ref()
+    }
+
+    /// The last connection error and when it happened.
+    pub(crate) fn last_error(&self) -> Option<&(String, Instant)> {
+        self.last_error.as_ref()
+    }
+
+    pub(crate) fn last_message_time(&self) -> Option<Instant> {
+        self.message_stats.last
+    }
+
+    pub(crate) fn message_count(&self) -> u64 {
+        self.message_stats.total
+    }
+
+    /// Delta messages per second, averaged over the last few seconds.
+    pub(crate) fn message_rate(&self) -> f32 {
+        self.message_stats.rate()
+    }
+
+    /// If the data source only sends a delta when stepped, like a stepped replay.
+    pub(crate) fn is_stepped(&self) -> bool {
+        self.source
+            .as_ref()
+            .is_some_and(|source| source.is_stepped())
+    }
+
+    /// Have a stepped data source send its next delta.
+    pub(crate) fn step(&mut self) {
+        if let Some(ref mut source) = self.source {
+            source.step();
+        }
+    }
+
+    /// Open the data source the server address names, or start discovery of a SignalK server.
+    fn connect(&mut self) {
+        match open_data_source(&self.server) {
+            Some(source) => {
+                self.state = ConnectionState::Connecting;
+                self.source = Some(source);
+            }
+            None => self.start_discovery(),
+        }
+    }
+
+    fn start_discovery(&mut self) {
+        self.state = ConnectionState::Discovering;
+        if self.connection.mode == EndpointMode::Direct {
+            // Nothing to ask the server, `handle_discovery` connects on the next frame.
+            return;
+        }
+        let mut request = ehttp::Request::get(&self.server);
+        self.authorize(&mut request);
+        let (signalk_tx, signalk_rx) = channel();
         self.discovery_rx = Some(signalk_rx);
-        ehttp::fetch(
-            request,
-            move |result: ehttp::Result<ehttp::Response>| match result {
-                Ok(response) => {
-                    let discovery: serde_json::Result<V1Discovery> =
-                        serde_json::from_slice(&response.bytes);
-                    if let Ok(discovery_value) = discovery {
-                        if let Err(e) = signalk_tx.send(discovery_value) {
-                            log::error!("Can't send discovery back {:?}", e);
-                        } else {
-                            log::info!("Discovery message sent");
-                        }
+        let server = self.server.clone();
+        ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
+            let discovery = result.and_then(|response| {
+                check_status(&response)?;
+                serde_json::from_slice::<V1Discovery>(&response.bytes).map_err(|err| {
+                    if let Ok(text) = from_utf8(&response.bytes) {
+                        log::warn!("Not a discovery document: {:?}", text);
                     }
-                }
-                Err(err) => {
-                    log::error!("Error: {:?}", err);
-                }
-            },
+                    format!(
+                        "{} didn't answer with a SignalK discovery document ({}), \
+                         check the address or use direct endpoints",
+                        server, err
+                    )
+                })
+            });
+            if let Err(e) = signalk_tx.send(discovery) {
+                log::error!("Can't send discovery back {:?}", e);
+            } else {
+                log::info!("Discovery message sent");
+            }
+        });
+    }
+
+    /// Drop the connections and try again after a delay that doubles with every failed attempt.
+    fn schedule_reconnect(&mut self, reason: &str) {
+        let delay = RECONNECT_BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(self.reconnect_attempts))
+            .min(RECONNECT_MAX_DELAY);
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
+        log::warn!(
+            "Connection to {} failed: {}, retrying in {:?}",
+            self.server,
+            reason,
+            delay
         );
+        self.discovery_rx = None;
+        self.full_rx = None;
+        self.ws_handler = None;
+        self.source = None;
+        let now = Instant::now();
+        self.last_error = Some((reason.to_string(), now));
+        let retry_at = now + delay;
+        self.state = if self.has_streamed {
+            ConnectionState::Reconnecting { retry_at }
+        } else {
+            ConnectionState::Error { retry_at }
+        };
     }
 
     pub(crate) fn handle_data(&mut self, ctx: &Context) {
+        self.handle_reconnect();
         self.handle_discovery(ctx);
         self.handle_full_message(ctx);
         self.handle_signalk_data();
+        self.handle_source();
+    }
+
+    fn handle_source(&mut self) {
+        let Some(ref mut source) = self.source else {
+            return;
+        };
+        match source.recv_deltas() {
+            Ok(deltas) => {
+                if deltas.is_empty() {
+                    return;
+                }
+                // There is no full model, start from an empty one with the deltas' vessel.
+                let storage = self.signalk_data.get_or_insert_with(|| {
+                    Storage::new(
+                        V1FullFormat::builder()
+                            .self_(source.self_context().to_string())
+                            .build(),
+                    )
+                });
+                let now = Instant::now();
+                for delta in deltas.iter() {
+                    if let Some(ref mut recorder) = self.recorder {
+                        recorder.record(&storage.data().self_, delta);
+                    }
+                    // Replayed and simulated deltas carry the times they were recorded or made
+                    // up, only the time they arrive tells how fresh they are.
+                    apply_delta(
+                        storage,
+                        &mut self.self_values,
+                        &mut self.history,
+                        delta,
+                        false,
+                    );
+                    self.message_stats.record(now);
+                }
+                self.state = ConnectionState::Streaming;
+                self.reconnect_attempts = 0;
+                self.has_streamed = true;
+            }
+            Err(reason) => self.schedule_reconnect(&reason),
+        }
+    }
+
+    fn handle_reconnect(&mut self) {
+        if let Some(retry_at) = self.state.retry_at() {
+            if Instant::now() >= retry_at {
+                log::info!(
+                    "Reconnecting to {}, attempt {}",
+                    self.server,
+                    self.reconnect_attempts
+                );
+                self.connect();
+            }
+        }
     }
 
     fn handle_signalk_data(&mut self) {
@@ -143,32 +828,87 @@ impl SignalKCommunicator {
         if let Some(ref mut storage) = self.signalk_data {
             //log::debug!("handle_signalk_data(): ");
             if let Some(ref mut ws_handler) = self.ws_handler {
+                ws_handler.update_subscriptions(&self.subscriptions);
                 // log::debug!("handle_signalk_data(): send message ");
-                ws_handler.recv_signalk_delta_messages(storage);
+                let received = ws_handler.recv_signalk_delta_messages(
+                    storage,
+                    &mut self.self_values,
+                    &mut self.history,
+                    self.recorder.as_mut(),
+                );
+                match received {
+                    Ok(true) => self.message_stats.record(Instant::now()),
+                    Ok(false) => {}
+                    Err(reason) => {
+                        self.schedule_reconnect(&reason);
+                        return;
+                    }
+                }
+                // Only a stream that opened counts as a working server.
+                if self.state == ConnectionState::ModelLoaded && ws_handler.opened {
+                    self.state = ConnectionState::Streaming;
+                    self.reconnect_attempts = 0;
+                    self.has_streamed = true;
+                }
             }
         }
     }
 
     fn handle_full_message(&mut self, ctx: &Context) {
         if let Some(ref mut full_rx_channel) = self.full_rx {
-            if let Ok(full) = full_rx_channel.try_recv() {
-                log::debug!("New sk full message");
-                ctx.request_repaint();
-                self.signalk_data = Some(Storage::new(full));
+            match full_rx_channel.try_recv() {
+                Ok(Ok(full)) => {
+                    log::debug!("New sk full message");
+                    ctx.request_repaint();
+                    let storage = Storage::new(full);
+                    self.full_model_self = storage
+                        .data()
+                        .get_self()
+                        .and_then(|vessel| serde_json::to_value(vessel).ok());
+                    self.signalk_data = Some(storage);
+                    self.full_model_time = Some(Instant::now());
+                    self.full_rx = None;
+                    self.state = ConnectionState::ModelLoaded;
+                }
+                Ok(Err(reason)) => self.schedule_reconnect(&reason),
+                Err(TryRecvError::Disconnected) => {
+                    self.schedule_reconnect("Full model request dropped")
+                }
+                Err(TryRecvError::Empty) => {}
             }
         }
     }
 
     fn handle_discovery(&mut self, ctx: &Context) {
+        if self.connection.mode == EndpointMode::Direct
+            && self.state == ConnectionState::Discovering
+        {
+            let ConnectionSettings {
+                http_endpoint,
+                ws_endpoint,
+                ..
+            } = self.connection.clone();
+            if http_endpoint.is_empty() || ws_endpoint.is_empty() {
+                self.schedule_reconnect(
+                    "Direct connection needs both a REST and a stream endpoint",
+                );
+            } else {
+                self.connect_endpoints(ctx, &http_endpoint, &ws_endpoint);
+            }
+            return;
+        }
         if let Some(ref mut discovery_rx_channel) = self.discovery_rx {
             match discovery_rx_channel.try_recv() {
-                Ok(discovery) => {
+                Ok(Ok(discovery)) => {
                     log::debug!("New discovery message");
+                    self.discovery_rx = None;
                     self.set_discovery(ctx, discovery);
                 }
-                Err(_) => {
-                    log::info!("Unable to recv discovery data");
+                Ok(Err(reason)) => self.schedule_reconnect(&reason),
+                Err(TryRecvError::Disconnected) => {
+                    self.schedule_reconnect("Discovery request dropped")
                 }
+                Err(TryRecvError::Empty) => {}
             }
         }
     }
@@ -176,12 +916,23 @@ impl SignalKCommunicator {
     fn set_discovery(&mut self, ctx: &Context, discovery: V1Discovery) {
         ctx.request_repaint();
         self.signalk_discovery = Some(discovery);
-        if let Some(ref endpoint) = self.get_http_endpoint() {
-            self.request_full_status(ctx, endpoint);
-        }
-        if let Some(ref endpoint) = self.get_ws_endpoint() {
-            self.setup_websocket_delta(ctx, endpoint);
+        let (Some(mut http_endpoint), Some(mut ws_endpoint)) =
+            (self.get_http_endpoint(), self.get_ws_endpoint())
+        else {
+            self.schedule_reconnect("Discovery has no v1 endpoints");
+            return;
+        };
+        if self.connection.mode == EndpointMode::RewriteHost {
+            http_endpoint = rewrite_host(&http_endpoint, &self.server);
+            ws_endpoint = rewrite_host(&ws_endpoint, &self.server);
         }
+        self.connect_endpoints(ctx, &http_endpoint, &ws_endpoint);
+    }
+
+    fn connect_endpoints(&mut self, ctx: &Context, http_endpoint: &String, ws_endpoint: &String) {
+        self.state = ConnectionState::FetchingFullModel;
+        self.request_full_status(ctx, http_endpoint);
+        self.setup_websocket_delta(ctx, ws_endpoint);
     }
 
     fn get_http_endpoint(&self) -> Option<String> {
@@ -199,56 +950,63 @@ impl SignalKCommunicator {
 
     fn setup_websocket_delta(&mut self, ctx: &Context, endpoint: &String) {
         log::debug!("Connect websocket to {:?}", endpoint);
-        let ws_url = endpoint.to_string();
+        // Only send what we subscribe to, not the server's default of everything.
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        let mut ws_url = format!("{}{}subscribe=none", endpoint, separator);
         let ctx_clone = ctx.clone();
         log::info!("Connect to websocket url: {}", ws_url);
         let wakeup = move || ctx_clone.request_repaint();
-        let options = ewebsock::Options::default();
+        let mut options = ewebsock::Options::default();
+        if let Some(token) = &self.token {
+            // Browsers can't set headers on a websocket, the server also takes the token
+            // as a query parameter.
+            if cfg!(target_arch = "wasm32") {
+                ws_url = format!("{}&token={}", ws_url, percent_encode(token));
+            } else {
+                options
+                    .additional_headers
+                    .push(("Authorization".to_string(), format!("Bearer {}", token)));
+            }
+        }
         match ewebsock::connect_with_wakeup(&ws_url, options, wakeup) {
             Ok((ws_sender, ws_receiver)) => {
                 log::debug!("Websocket connected ok!");
                 self.ws_handler = Some(WebsocketHandler {
                     ws_receiver,
                     ws_sender,
+                    opened: false,
+                    subscribed: None,
                 });
             }
             Err(error) => {
                 log::error!("Failed to connect to {:?}: {}", &ws_url, error);
+                self.schedule_reconnect(&error);
             }
         }
     }
 
     fn request_full_status(&mut self, ctx: &Context, endpoint: &String) {
-        let request = ehttp::Request::get(endpoint);
-        let (full_sk_tx, full_sk_rx): (Sender<V1FullFormat>, Receiver<V1FullFormat>) = channel();
+        let mut request = ehttp::Request::get(endpoint);
+        self.authorize(&mut request);
+        let (full_sk_tx, full_sk_rx) = channel();
         self.full_rx = Some(full_sk_rx);
-        self.discovery_rx = None;
         let ctx_clone = ctx.clone();
-        ehttp::fetch(
-            request,
-            move |result: ehttp::Result<ehttp::Response>| match result {
-                Ok(response) => {
-                    log::debug!("Full Got: {:?}", response);
-                    let full: serde_json::Result<V1FullFormat> =
-                        serde_json::from_slice(&response.bytes);
-                    log::debug!("Full data: {:?}", full);
-                    if let Ok(full_value) = full {
-                        ctx_clone.request_repaint();
-                        if let Err(err) = full_sk_tx.send(full_value) {
-                            log::error!("Can't send full back {:?}", err)
-                        }
-                    } else {
-                        if let Ok(full_message) = from_utf8(&response.bytes) {
-                            log::warn!("Cant parse: {:?}", full_message);
-                        }
-                        log::warn!("Full Got: {:?}", full);
+        ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
+            let full = result.and_then(|response| {
+                log::debug!("Full Got: {:?}", response);
+                check_status(&response)?;
+                serde_json::from_slice::<V1FullFormat>(&response.bytes).map_err(|err| {
+                    if let Ok(full_message) = from_utf8(&response.bytes) {
+                        log::warn!("Cant parse: {:?}", full_message);
                     }
-                }
-                Err(err) => {
-                    log::debug!("Get full error: {:?}", err);
-                }
-            },
-        );
+                    format!("Can't parse full model: {}", err)
+                })
+            });
+            ctx_clone.request_repaint();
+            if let Err(err) = full_sk_tx.send(full) {
+                log::error!("Can't send full back {:?}", err)
+            }
+        });
     }
 
     pub(crate) fn get_f64_for_path(&self, path: String) -> Result<f64, WebSocketError> {
@@ -257,6 +1015,10 @@ impl SignalKCommunicator {
             let res = storage.get_f64_for_path(path.clone());
             if let Err(e) = res {
                 log::info!("get_f64_for_path: Err({:?})", e);
+                // Storage doesn't model every path, use the raw delta value if we got one.
+                if let Some(value) = self.self_values.get(&path).and_then(|v| v.value.as_f64()) {
+                    return Ok(value);
+                }
                 match e {
                     SignalKGetError::NoSuchPath => Err(WebSocketError::NoSuchPath),
                     SignalKGetError::WrongDataType => Err(WebSocketError::WrongDataType),
@@ -275,4 +1037,177 @@ impl SignalKCommunicator {
             Err(WebSocketError::ServerNotCreated)
         }
     }
+
+    /// Like `get_f64_for_path`, but a value older than `max_age` is `WebSocketError::Stale`.
+    pub(crate) fn get_fresh_f64_for_path(
+        &self,
+        path: String,
+        max_age: Duration,
+    ) -> Result<f64, WebSocketError> {
+        let stale = self.is_stale(&path, max_age);
+        let value = self.get_f64_for_path(path)?;
+        if stale {
+            Err(WebSocketError::Stale)
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// How old the value of a path of our own vessel is. The age counts from the value's
+    /// timestamp when it has one, or else from when it was received. Values only in the full
+    /// model count from when the full model was received.
+    pub(crate) fn path_age(&self, path: &str) -> Option<Duration> {
+        let (timestamp, time) = match self.self_values.get(path) {
+            Some(received) => (received.timestamp, received.time),
+            None => (self.full_model_timestamp(path), self.full_model_time?),
+        };
+        Some(match timestamp {
+            // A clock ahead of ours makes the value new, not negative in age.
+            Some(timestamp) => SystemTime::now()
+                .duration_since(timestamp)
+                .unwrap_or_default(),
+            None => time.elapsed(),
+        })
+    }
+
+    /// The timestamp of a value of our own vessel in the full model.
+    fn full_model_timestamp(&self, path: &str) -> Option<SystemTime> {
+        let pointer = format!(
+            "/{}/timestamp",
+            path.strip_prefix("self.")?.replace('.', "/")
+        );
+        self.full_model_self
+            .as_ref()?
+            .pointer(&pointer)?
+            .as_str()
+            .and_then(parse_timestamp)
+    }
+
+    pub(crate) fn is_stale(&self, path: &str, max_age: Duration) -> bool {
+        self.path_age(path).is_some_and(|age| age > max_age)
+    }
+
+    /// The latest text value received for a path of our own vessel, e.g. `self.environment.sun`.
+    pub(crate) fn get_string_for_path(&self, path: &str) -> Option<String> {
+        self.self_values
+            .get(path)
+            .and_then(|received| received.value.as_str())
+            .map(str::to_string)
+    }
+
+    /// Latitude and longitude of our own vessel in degrees.
+    pub(crate) fn get_self_position(&self) -> Option<(f64, f64)> {
+        let storage = self.signalk_data.as_ref()?;
+        let navigation = storage.data().get_self()?.navigation.as_ref()?;
+        let position = navigation.position.as_ref()?.value.as_ref()?;
+        Some((position.latitude, position.longitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix_secs(text: &str) -> Option<f64> {
+        parse_timestamp(text).map(|time| time.duration_since(UNIX_EPOCH).unwrap().as_secs_f64())
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(unix_secs("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(unix_secs("2024-01-01T00:00:00Z"), Some(1_704_067_200.0));
+        assert_eq!(unix_secs("2024-02-29T12:30:15.5Z"), Some(1_709_209_815.5));
+        assert_eq!(
+            unix_secs("2024-01-01T02:00:00+02:00"),
+            Some(1_704_067_200.0)
+        );
+        assert_eq!(
+            unix_secs("2023-12-31T22:00:00-02:00"),
+            Some(1_704_067_200.0)
+        );
+    }
+
+    #[test]
+    fn normalizes_server_urls() {
+        for (input, url) in [
+            ("demo.signalk.org", "http://demo.signalk.org/signalk"),
+            ("  host:3000  ", "http://host:3000/signalk"),
+            ("https://host:3443/", "https://host:3443/signalk"),
+            ("ws://host:3000/signalk/", "http://host:3000/signalk"),
+            ("WSS://host", "https://host/signalk"),
+            ("http://host/custom/path", "http://host/custom/path"),
+            ("http://[::1]:3000", "http://[::1]:3000/signalk"),
+            ("http://[fe80::1]", "http://[fe80::1]/signalk"),
+            ("tcp://host:10110", "tcp://host:10110"),
+        ] {
+            assert_eq!(
+                normalize_server_url(input),
+                Ok(url.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_server_urls() {
+        for input in [
+            "",
+            "   ",
+            "ftp://host",
+            "http:///signalk",
+            "http://host:99999",
+            "http://host:abc/signalk",
+            "http://user@host",
+            "http://my host",
+        ] {
+            assert!(normalize_server_url(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rewrites_endpoint_hosts() {
+        for (endpoint, server, rewritten) in [
+            (
+                "http://10.0.0.5:3000/signalk/v1/api/",
+                "http://boat:8080/signalk",
+                "http://boat:8080/signalk/v1/api/",
+            ),
+            (
+                "ws://10.0.0.5:3000/signalk/v1/stream",
+                "https://boat.example.com/signalk",
+                "wss://boat.example.com/signalk/v1/stream",
+            ),
+            (
+                "wss://internal/signalk/v1/stream",
+                "http://boat/signalk",
+                "ws://boat/signalk/v1/stream",
+            ),
+            (
+                "https://internal/signalk/v1/api/",
+                "http://[::1]:3000/signalk",
+                "http://[::1]:3000/signalk/v1/api/",
+            ),
+            ("10.0.0.5:3000", "https://boat", "https://boat"),
+        ] {
+            assert_eq!(rewrite_host(endpoint, server), rewritten, "{}", endpoint);
+        }
+    }
+
+    #[test]
+    fn percent_encodes_tokens() {
+        assert_eq!(percent_encode("abc-123_.~"), "abc-123_.~");
+        assert_eq!(percent_encode("a+b/c=="), "a%2Bb%2Fc%3D%3D");
+        assert_eq!(percent_encode("a b&c"), "a%20b%26c");
+    }
+
+    #[test]
+    fn rejects_bad_timestamps() {
+        assert_eq!(unix_secs(""), None);
+        assert_eq!(unix_secs("2024-01-01"), None);
+        assert_eq!(unix_secs("2024-13-01T00:00:00Z"), None);
+        assert_eq!(unix_secs("2024-01-01T25:00:00Z"), None);
+        assert_eq!(unix_secs("2024-01-01T00:00:00"), None);
+        assert_eq!(unix_secs("1969-12-31T23:59:59Z"), None);
+    }
 }
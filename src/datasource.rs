@@ -0,0 +1,364 @@
+use signalk::V1DeltaFormat;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::recording::ReplaySource;
+use crate::simulator::SimulatorSource;
+
+/// The context of deltas from data sources, the vessel the instruments are on. `Storage`
+/// only takes vessel ids in the `urn:mrn` form.
+pub(crate) const LOCAL_CONTEXT: &str =
+    "vessels.urn:mrn:signalk:uuid:00000000-0000-4000-8000-000000000000";
+
+/// Somewhere other than a SignalK server that deltas come from, picked by the scheme of
+/// the server address.
+pub(crate) trait DeltaSource {
+    /// The deltas received since the last call, or why the source stopped.
+    fn recv_deltas(&mut self) -> Result<Vec<V1DeltaFormat>, String>;
+
+    /// The context of our own vessel in the deltas.
+    fn self_context(&self) -> &str {
+        LOCAL_CONTEXT
+    }
+
+    /// If the source only sends a delta when `step` is called.
+    fn is_stepped(&self) -> bool {
+        false
+    }
+
+    /// Send the next delta, for stepped sources.
+    fn step(&mut self) {}
+}
+
+/// The schemes of the server addresses that are data sources instead of SignalK servers.
+#[cfg(not(target_arch = "wasm32"))]
+const SOURCE_SCHEMES: [&str; 6] = ["tcp", "udp", "serial", "file", "replay", "sim"];
+#[cfg(target_arch = "wasm32")]
+const SOURCE_SCHEMES: [&str; 1] = ["sim"];
+
+/// Is the address a data source instead of a SignalK server.
+pub(crate) fn is_data_source(address: &str) -> bool {
+    address
+        .split_once("://")
+        .is_some_and(|(scheme, _)| SOURCE_SCHEMES.contains(&scheme.to_lowercase().as_str()))
+}
+
+/// Open the data source for an address, or `None` if it is a SignalK server. Problems
+/// opening it come from the first `recv_deltas`.
+pub(crate) fn open_data_source(address: &str) -> Option<Box<dyn DeltaSource>> {
+    let (scheme, rest) = address.split_once("://")?;
+    let source: Box<dyn DeltaSource> = match scheme.to_lowercase().as_str() {
+        #[cfg(not(target_arch = "wasm32"))]
+        "tcp" => Box::new(nmea_stream::NmeaSource::tcp(rest)),
+        #[cfg(not(target_arch = "wasm32"))]
+        "udp" => Box::new(nmea_stream::NmeaSource::udp(rest)),
+        #[cfg(not(target_arch = "wasm32"))]
+        "serial" => Box::new(nmea_stream::NmeaSource::serial(rest)),
+        #[cfg(not(target_arch = "wasm32"))]
+        "file" => Box::new(nmea_stream::NmeaSource::file(rest)),
+        #[cfg(not(target_arch = "wasm32"))]
+        "replay" => Box::new(ReplaySource::open(rest)),
+        "sim" => Box::new(SimulatorSource::open(rest)),
+        _ => return None,
+    };
+    Some(source)
+}
+
+/// NMEA 0183 sentences read on a background thread, from a multiplexer on the network,
+/// a serial port or a recorded file.
+#[cfg(not(target_arch = "wasm32"))]
+mod nmea_stream {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, ErrorKind, Read};
+    use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use signalk::V1DeltaFormat;
+
+    use super::DeltaSource;
+    use crate::nmea::sentence_to_delta;
+
+    /// How long a read waits before checking if the source has been dropped.
+    const READ_TIMEOUT: Duration = Duration::from_secs(1);
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+    /// The time between the lines of a recorded file, as it has no times of its own.
+    const FILE_LINE_INTERVAL: Duration = Duration::from_millis(100);
+
+    type DeltaSender = Sender<Result<V1DeltaFormat, String>>;
+
+    pub(super) struct NmeaSource {
+        delta_rx: Receiver<Result<V1DeltaFormat, String>>,
+        /// Tells the reader thread to stop when the source is dropped.
+        stop: Arc<AtomicBool>,
+    }
+
+    impl NmeaSource {
+        fn spawn(
+            read: impl FnOnce(&DeltaSender, &AtomicBool) -> Result<(), String> + Send + 'static,
+        ) -> Self {
+            let (delta_tx, delta_rx) = channel();
+            let stop = Arc::new(AtomicBool::new(false));
+            let thread_stop = stop.clone();
+            std::thread::spawn(move || {
+                if let Err(reason) = read(&delta_tx, &thread_stop) {
+                    // The source may be gone already, then nobody wants to know.
+                    let _ = delta_tx.send(Err(reason));
+                }
+            });
+            Self { delta_rx, stop }
+        }
+
+        /// A multiplexer serving sentences on a TCP port, like `host:10110`.
+        pub(super) fn tcp(address: &str) -> Self {
+            let address = address.trim_end_matches('/').to_string();
+            Self::spawn(move |delta_tx, stop| {
+                let socket_address = address
+                    .to_socket_addrs()
+                    .map_err(|err| format!("Can't resolve {}: {}", address, err))?
+                    .next()
+                    .ok_or_else(|| format!("No address for {}", address))?;
+                let stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)
+                    .map_err(|err| format!("Can't connect to {}: {}", address, err))?;
+                stream
+                    .set_read_timeout(Some(READ_TIMEOUT))
+                    .map_err(|err| err.to_string())?;
+                log::info!("Reading NMEA 0183 from tcp://{}", address);
+                read_lines(BufReader::new(stream), delta_tx, stop)
+            })
+        }
+
+        /// Sentences broadcast as datagrams, listening on an address like `0.0.0.0:10110`.
+        pub(super) fn udp(address: &str) -> Self {
+            let address = address.trim_end_matches('/').to_string();
+            Self::spawn(move |delta_tx, stop| {
+                let socket = UdpSocket::bind(&address)
+                    .map_err(|err| format!("Can't listen on {}: {}", address, err))?;
+                socket
+                    .set_read_timeout(Some(READ_TIMEOUT))
+                    .map_err(|err| err.to_string())?;
+                log::info!("Reading NMEA 0183 from udp://{}", address);
+                let mut buffer = [0u8; 65536];
+                while !stop.load(Ordering::Relaxed) {
+                    match socket.recv(&mut buffer) {
+                        Ok(length) => {
+                            for line in String::from_utf8_lossy(&buffer[..length]).lines() {
+                                send_sentence(line, delta_tx)?;
+                            }
+                        }
+                        Err(err) if is_timeout(&err) => {}
+                        Err(err) => return Err(format!("Reading {} failed: {}", address, err)),
+                    }
+                }
+                Ok(())
+            })
+        }
+
+        /// A serial port device, like `/dev/ttyUSB0`. The port isn't configured, set the
+        /// speed first, for example with `stty -F /dev/ttyUSB0 4800 raw`.
+        pub(super) fn serial(path: &str) -> Self {
+            let path = path.to_string();
+            Self::spawn(move |delta_tx, stop| {
+                let port =
+                    open_serial(&path).map_err(|err| format!("Can't open {}: {}", path, err))?;
+                log::info!("Reading NMEA 0183 from serial://{}", path);
+                read_lines(BufReader::new(port), delta_tx, stop)
+            })
+        }
+
+        /// A file of recorded sentences, played over and over a line at a time.
+        pub(super) fn file(path: &str) -> Self {
+            let path = path.to_string();
+            Self::spawn(move |delta_tx, stop| {
+                let recording = std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Can't read {}: {}", path, err))?;
+                let lines = recording
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    return Err(format!("{} has no NMEA 0183 sentences", path));
+                }
+                log::info!("Playing NMEA 0183 from file://{}", path);
+                while !stop.load(Ordering::Relaxed) {
+                    for line in lines.iter() {
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        send_sentence(line, delta_tx)?;
+                        std::thread::sleep(FILE_LINE_INTERVAL);
+                    }
+                }
+                Ok(())
+            })
+        }
+    }
+
+    impl Drop for NmeaSource {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl DeltaSource for NmeaSource {
+        fn recv_deltas(&mut self) -> Result<Vec<V1DeltaFormat>, String> {
+            let mut deltas = Vec::new();
+            loop {
+                match self.delta_rx.try_recv() {
+                    Ok(Ok(delta)) => deltas.push(delta),
+                    Ok(Err(reason)) => return Err(reason),
+                    Err(TryRecvError::Empty) => return Ok(deltas),
+                    Err(TryRecvError::Disconnected) => {
+                        return Err("NMEA 0183 source closed".to_string())
+                    }
+                }
+            }
+        }
+    }
+
+    /// A serial port whose reads give up after `READ_TIMEOUT`, like the socket reads, so
+    /// the reader notices when the source is dropped.
+    #[cfg(unix)]
+    struct SerialPort(File);
+
+    #[cfg(unix)]
+    impl Read for SerialPort {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            use std::os::unix::io::AsRawFd;
+            let mut poll_fd = libc::pollfd {
+                fd: self.0.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: a single valid pollfd, for a file that outlives the call.
+            let ready =
+                unsafe { libc::poll(&mut poll_fd, 1, READ_TIMEOUT.as_millis() as libc::c_int) };
+            match ready {
+                -1 => Err(std::io::Error::last_os_error()),
+                0 => Err(ErrorKind::TimedOut.into()),
+                _ => self.0.read(buffer),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn open_serial(path: &str) -> std::io::Result<impl Read> {
+        File::open(path).map(SerialPort)
+    }
+
+    /// Without a timeout, the reader only stops after the next line once dropped.
+    #[cfg(not(unix))]
+    fn open_serial(path: &str) -> std::io::Result<impl Read> {
+        File::open(path)
+    }
+
+    fn is_timeout(err: &std::io::Error) -> bool {
+        matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+    }
+
+    /// Send the delta for a sentence. Bad sentences are skipped, an error means the
+    /// source has been dropped.
+    fn send_sentence(line: &str, delta_tx: &DeltaSender) -> Result<(), String> {
+        match sentence_to_delta(line) {
+            Ok(Some(delta)) => delta_tx
+                .send(Ok(delta))
+                .map_err(|_| "NMEA 0183 source dropped".to_string()),
+            Ok(None) => Ok(()),
+            Err(reason) => {
+                log::debug!("Skipping sentence: {}", reason);
+                Ok(())
+            }
+        }
+    }
+
+    /// Read sentences a line at a time until the stream ends or the source is dropped.
+    fn read_lines(
+        mut reader: impl BufRead,
+        delta_tx: &DeltaSender,
+        stop: &AtomicBool,
+    ) -> Result<(), String> {
+        let mut line = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            // A read that times out keeps what it got in `line`, the rest comes next time.
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return Err("NMEA 0183 stream ended".to_string()),
+                Ok(_) => {
+                    // Line noise that isn't text fails the checksum, like other bad sentences.
+                    send_sentence(&String::from_utf8_lossy(&line), delta_tx)?;
+                    line.clear();
+                }
+                Err(err) if is_timeout(&err) => {}
+                Err(err) => return Err(format!("Reading NMEA 0183 failed: {}", err)),
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::Write;
+        use std::net::TcpListener;
+        use std::time::Instant;
+
+        #[test]
+        fn reads_sentences_from_tcp() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let serving = std::thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                // Good sentences around line noise and a bad checksum, which are skipped.
+                stream
+                    .write_all(b"$IIHDT,90.0,T*1B\r\n\xff\xfe\x80noise\r\n$IIHDT,1*00\r\n")
+                    .unwrap();
+                stream.write_all(b"$IIVHW,,T,,M,5.0,N,,K\r\n").unwrap();
+                stream
+            });
+            let mut source = NmeaSource::tcp(&address.to_string());
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut paths = Vec::new();
+            while paths.len() < 2 && Instant::now() < deadline {
+                for delta in source.recv_deltas().unwrap() {
+                    for update in delta.updates {
+                        paths.extend(update.values.into_iter().flatten().map(|value| value.path));
+                    }
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(
+                paths,
+                vec!["navigation.headingTrue", "navigation.speedThroughWater"]
+            );
+
+            // Closing the connection ends the source.
+            drop(serving.join().unwrap());
+            let mut result = source.recv_deltas();
+            while matches!(result, Ok(ref deltas) if deltas.is_empty()) && Instant::now() < deadline
+            {
+                std::thread::sleep(Duration::from_millis(10));
+                result = source.recv_deltas();
+            }
+            assert_eq!(result.unwrap_err(), "NMEA 0183 stream ended");
+        }
+
+        #[test]
+        fn rejects_files_without_sentences() {
+            let path = std::env::temp_dir().join(format!("nmea-empty-{}.txt", std::process::id()));
+            std::fs::write(&path, "\n  \r\n\n").unwrap();
+            let mut source = NmeaSource::file(path.to_str().unwrap());
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut result = source.recv_deltas();
+            while matches!(result, Ok(ref deltas) if deltas.is_empty()) && Instant::now() < deadline
+            {
+                std::thread::sleep(Duration::from_millis(10));
+                result = source.recv_deltas();
+            }
+            let _ = std::fs::remove_file(&path);
+            assert_eq!(
+                result.unwrap_err(),
+                format!("{} has no NMEA 0183 sentences", path.display())
+            );
+        }
+    }
+}
@@ -28,7 +28,7 @@
 //  STW           | Boat Speed aka Speed Through Water      | *+ self.navigation.speedThroughWater
 //  STR           | The steep pilot                         | ??
 //  TRP           | A running tally of distance travel since last reset | +self.navigation.trip.log
-//  TWA           | True wind angle from bow                | *+self.environment.wind.angleTrueGround
+//  TWA           | True wind angle from bow                | *+self.environment.wind.angleTrueWater, angleTrueGround
 //  TWD           | True wind direction rel north           | *+self.environment.wind.directionTrue
 //  TWS           | True wind speed relative vessel         | *+self.environment.wind.speedTrue
 //  UTC           | Universal time coordinated              | !!+self.navigation.datetime
@@ -36,7 +36,7 @@
 //  WND           | Velocity made good upwind               |
 //  XTE           | Cross track error                       | !!self.navigation.course.crossTrackError, navigation.course.calcValues.crossTrackError
 
-use crate::communication::SignalKCommunicator;
+use crate::communication::{PathSubscription, SignalKCommunicator, WebSocketError};
 use crate::datavalues::{
     AirTemperature, Altitude, ApparentWindAngle, ApparentWindSpeed, Barometer, Battery,
     BearingTrue, CourseOverGround, CrossTrackError, DataValue, DepthOfWater,
@@ -47,7 +47,7 @@ use crate::datavalues::{
 };
 use egui::Ui;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum DataValues {
     AirTemperature(AirTemperature),
     ApparentWindAngle(ApparentWindAngle),
@@ -183,6 +183,111 @@ impl DataValues {
         }
     }
 
+    /// The SignalK paths the value is read from, in order of preference.
+    pub fn paths(&self) -> Vec<String> {
+        match &self {
+            DataValues::SpeedThroughWater(value) => value.paths(),
+            DataValues::SpeedOverGround(value) => value.paths(),
+            DataValues::CourseOverGround(value) => value.paths(),
+            DataValues::WaterTemperature(value) => value.paths(),
+            DataValues::AirTemperature(value) => value.paths(),
+            DataValues::ApparentWindAngle(value) => value.paths(),
+            DataValues::ApparentWindSpeed(value) => value.paths(),
+            DataValues::Barometer(value) => value.paths(),
+            DataValues::Battery(value) => value.paths(),
+            DataValues::BearingTrue(value) => value.paths(),
+            DataValues::DistanceTraveled(value) => value.paths(),
+            DataValues::DepthOfWater(value) => value.paths(),
+            DataValues::SpeedOfCurrent(value) => value.paths(),
+            DataValues::DistanceToWaypoint(value) => value.paths(),
+            DataValues::Altitude(value) => value.paths(),
+            DataValues::DirectionOfWindRelativeGround(value) => value.paths(),
+            DataValues::SpeedOfWindRelativeGround(value) => value.paths(),
+            DataValues::HeadingTrue(value) => value.paths(),
+            DataValues::Odometer(value) => value.paths(),
+            DataValues::Position(value) => value.paths(),
+            DataValues::RudderAngle(value) => value.paths(),
+            DataValues::Trip(value) => value.paths(),
+            DataValues::TrueWindAngleFromBow(value) => value.paths(),
+            DataValues::TrueWindDirectionRelNorth(value) => value.paths(),
+            DataValues::TrueWindSpeed(value) => value.paths(),
+            DataValues::UniversalTimeCoordinated(value) => value.paths(),
+            DataValues::VelocityMadeGood(value) => value.paths(),
+            DataValues::VelocityMadeGoodUpwind(value) => value.paths(),
+            DataValues::CrossTrackError(value) => value.paths(),
+        }
+    }
+
+    /// The updates to ask the server for to show this value.
+    pub fn subscriptions(&self) -> Vec<PathSubscription> {
+        match &self {
+            DataValues::SpeedThroughWater(value) => value.subscriptions(),
+            DataValues::SpeedOverGround(value) => value.subscriptions(),
+            DataValues::CourseOverGround(value) => value.subscriptions(),
+            DataValues::WaterTemperature(value) => value.subscriptions(),
+            DataValues::AirTemperature(value) => value.subscriptions(),
+            DataValues::ApparentWindAngle(value) => value.subscriptions(),
+            DataValues::ApparentWindSpeed(value) => value.subscriptions(),
+            DataValues::Barometer(value) => value.subscriptions(),
+            DataValues::Battery(value) => value.subscriptions(),
+            DataValues::BearingTrue(value) => value.subscriptions(),
+            DataValues::DistanceTraveled(value) => value.subscriptions(),
+            DataValues::DepthOfWater(value) => value.subscriptions(),
+            DataValues::SpeedOfCurrent(value) => value.subscriptions(),
+            DataValues::DistanceToWaypoint(value) => value.subscriptions(),
+            DataValues::Altitude(value) => value.subscriptions(),
+            DataValues::DirectionOfWindRelativeGround(value) => value.subscriptions(),
+            DataValues::SpeedOfWindRelativeGround(value) => value.subscriptions(),
+            DataValues::HeadingTrue(value) => value.subscriptions(),
+            DataValues::Odometer(value) => value.subscriptions(),
+            DataValues::Position(value) => value.subscriptions(),
+            DataValues::RudderAngle(value) => value.subscriptions(),
+            DataValues::Trip(value) => value.subscriptions(),
+            DataValues::TrueWindAngleFromBow(value) => value.subscriptions(),
+            DataValues::TrueWindDirectionRelNorth(value) => value.subscriptions(),
+            DataValues::TrueWindSpeed(value) => value.subscriptions(),
+            DataValues::UniversalTimeCoordinated(value) => value.subscriptions(),
+            DataValues::VelocityMadeGood(value) => value.subscriptions(),
+            DataValues::VelocityMadeGoodUpwind(value) => value.subscriptions(),
+            DataValues::CrossTrackError(value) => value.subscriptions(),
+        }
+    }
+
+    /// Format a value in SI units in the selected display unit.
+    pub fn format_value(&self, raw: Result<f64, WebSocketError>) -> String {
+        match &self {
+            DataValues::SpeedThroughWater(value) => value.format_value(raw),
+            DataValues::SpeedOverGround(value) => value.format_value(raw),
+            DataValues::CourseOverGround(value) => value.format_value(raw),
+            DataValues::WaterTemperature(value) => value.format_value(raw),
+            DataValues::AirTemperature(value) => value.format_value(raw),
+            DataValues::ApparentWindAngle(value) => value.format_value(raw),
+            DataValues::ApparentWindSpeed(value) => value.format_value(raw),
+            DataValues::Barometer(value) => value.format_value(raw),
+            DataValues::Battery(value) => value.format_value(raw),
+            DataValues::BearingTrue(value) => value.format_value(raw),
+            DataValues::DistanceTraveled(value) => value.format_value(raw),
+            DataValues::DepthOfWater(value) => value.format_value(raw),
+            DataValues::SpeedOfCurrent(value) => value.format_value(raw),
+            DataValues::DistanceToWaypoint(value) => value.format_value(raw),
+            DataValues::Altitude(value) => value.format_value(raw),
+            DataValues::DirectionOfWindRelativeGround(value) => value.format_value(raw),
+            DataValues::SpeedOfWindRelativeGround(value) => value.format_value(raw),
+            DataValues::HeadingTrue(value) => value.format_value(raw),
+            DataValues::Odometer(value) => value.format_value(raw),
+            DataValues::Position(value) => value.format_value(raw),
+            DataValues::RudderAngle(value) => value.format_value(raw),
+            DataValues::Trip(value) => value.format_value(raw),
+            DataValues::TrueWindAngleFromBow(value) => value.format_value(raw),
+            DataValues::TrueWindDirectionRelNorth(value) => value.format_value(raw),
+            DataValues::TrueWindSpeed(value) => value.format_value(raw),
+            DataValues::UniversalTimeCoordinated(value) => value.format_value(raw),
+            DataValues::VelocityMadeGood(value) => value.format_value(raw),
+            DataValues::VelocityMadeGoodUpwind(value) => value.format_value(raw),
+            DataValues::CrossTrackError(value) => value.format_value(raw),
+        }
+    }
+
     pub fn name(&self) -> String {
         match &self {
             DataValues::SpeedThroughWater(value) => value.name(),
@@ -251,115 +356,53 @@ impl DataValues {
         }
     }
 
-    pub fn add_config_values(self: &mut DataValues, ui: &mut Ui) {
-        ui.selectable_value(
-            self,
+    /// One of every kind of value, with the default settings, in the order they are offered.
+    pub fn all() -> Vec<DataValues> {
+        vec![
             DataValues::AirTemperature(AirTemperature::default()),
-            "AIR",
-        );
-        ui.selectable_value(
-            self,
             DataValues::ApparentWindAngle(ApparentWindAngle::default()),
-            "AWA",
-        );
-        ui.selectable_value(
-            self,
             DataValues::ApparentWindSpeed(ApparentWindSpeed::default()),
-            "AWS",
-        );
-        ui.selectable_value(self, DataValues::Barometer(Barometer::default()), "BAR");
-        ui.selectable_value(self, DataValues::Battery(Battery::default()), "BAT");
-        ui.selectable_value(self, DataValues::BearingTrue(BearingTrue::default()), "BTW");
-        ui.selectable_value(
-            self,
+            DataValues::Barometer(Barometer::default()),
+            DataValues::Battery(Battery::default()),
+            DataValues::BearingTrue(BearingTrue::default()),
             DataValues::DistanceTraveled(DistanceTraveled::default()),
-            "DIS",
-        );
-        ui.selectable_value(
-            self,
             DataValues::DepthOfWater(DepthOfWater::default()),
-            "DPT",
-        );
-        ui.selectable_value(
-            self,
             DataValues::CourseOverGround(CourseOverGround::default()),
-            "COG",
-        );
-        ui.selectable_value(
-            self,
             DataValues::SpeedOverGround(SpeedOverGround::default()),
-            "SOG",
-        );
-        ui.selectable_value(
-            self,
             DataValues::SpeedOfCurrent(SpeedOfCurrent::default()),
-            "DRF",
-        );
-        ui.selectable_value(
-            self,
             DataValues::DistanceToWaypoint(DistanceToWaypoint::default()),
-            "DTW",
-        );
-        ui.selectable_value(self, DataValues::Altitude(Altitude::default()), "ELV");
-        ui.selectable_value(
-            self,
+            DataValues::Altitude(Altitude::default()),
             DataValues::DirectionOfWindRelativeGround(DirectionOfWindRelativeGround::default()),
-            "GWD",
-        );
-        ui.selectable_value(
-            self,
             DataValues::SpeedOfWindRelativeGround(SpeedOfWindRelativeGround::default()),
-            "GWS",
-        );
-        ui.selectable_value(self, DataValues::HeadingTrue(HeadingTrue::default()), "HDG");
-        ui.selectable_value(self, DataValues::Odometer(Odometer::default()), "ODO");
-        ui.selectable_value(self, DataValues::Position(Position::default()), "POS");
-        ui.selectable_value(self, DataValues::RudderAngle(RudderAngle::default()), "RUD");
-        ui.selectable_value(self, DataValues::Trip(Trip::default()), "TRP");
-        ui.selectable_value(
-            self,
+            DataValues::HeadingTrue(HeadingTrue::default()),
+            DataValues::Odometer(Odometer::default()),
+            DataValues::Position(Position::default()),
+            DataValues::RudderAngle(RudderAngle::default()),
+            DataValues::Trip(Trip::default()),
             DataValues::TrueWindAngleFromBow(TrueWindAngleFromBow::default()),
-            "TWA",
-        );
-        ui.selectable_value(
-            self,
             DataValues::TrueWindDirectionRelNorth(TrueWindDirectionRelNorth::default()),
-            "TWD",
-        );
-        ui.selectable_value(
-            self,
             DataValues::TrueWindSpeed(TrueWindSpeed::default()),
-            "TWS",
-        );
-        ui.selectable_value(
-            self,
             DataValues::UniversalTimeCoordinated(UniversalTimeCoordinated::default()),
-            "UTC",
-        );
-        ui.selectable_value(
-            self,
             DataValues::VelocityMadeGood(VelocityMadeGood::default()),
-            "VMG",
-        );
-        ui.selectable_value(
-            self,
             DataValues::VelocityMadeGoodUpwind(VelocityMadeGoodUpwind::default()),
-            "WND",
-        );
-        ui.selectable_value(
-            self,
             DataValues::CrossTrackError(CrossTrackError::default()),
-            "XTE",
-        );
-        ui.selectable_value(
-            self,
             DataValues::SpeedThroughWater(SpeedThroughWater::default()),
-            "STW",
-        );
-        ui.selectable_value(
-            self,
             DataValues::WaterTemperature(WaterTemperature::default()),
-            "SEA",
-        );
+        ]
+    }
+
+    pub fn add_config_values(self: &mut DataValues, ui: &mut Ui) {
+        for value in DataValues::all() {
+            // Compare the kind only, the settings of the current value may differ from the
+            // defaults. Picking the current kind again keeps them.
+            let selected = std::mem::discriminant(self) == std::mem::discriminant(&value);
+            if ui
+                .selectable_label(selected, value.abbreviation())
+                .clicked()
+                && !selected
+            {
+                *self = value;
+            }
+        }
     }
 }
@@ -1,7 +1,7 @@
 use crate::communication::WebSocketError;
 use eframe::epaint::text::TextWrapMode;
 use egui::Ui;
-use log::{debug, info};
+use log::info;
 use signalk::definitions::V1DateTime;
 use signalk::V1PositionType;
 
@@ -11,7 +11,7 @@ pub trait DataUnit {
     fn format(&self, value: Result<f64, WebSocketError>) -> String;
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum SpeedUnit {
     MeterPerSecond,
     Knot,
@@ -76,7 +76,7 @@ impl DataUnit for SpeedUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum AngularUnit {
     Radians,
     Degrees,
@@ -135,7 +135,7 @@ impl DataUnit for AngularUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum DistanceUnit {
     Meters,
     NauticalMile,
@@ -227,7 +227,7 @@ impl DataUnit for DistanceUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum TemperatureUnit {
     Celsius,
     Fahrenheit,
@@ -290,7 +290,7 @@ impl DataUnit for TemperatureUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum PressureUnit {
     HectoPascal,
     Millibar,
@@ -351,7 +351,7 @@ impl DataUnit for PressureUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum VoltageUnit {
     Volt,
 }
@@ -371,7 +371,7 @@ impl DataUnit for VoltageUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum PositionUnit {
     DecimalDegrees,
     DegreesMinutesSeconds,
@@ -497,7 +497,7 @@ impl DataUnit for PositionUnit {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum DateTimeUnit {
     Default,
 }
@@ -1,27 +1,130 @@
-use crate::communication::WebSocketError::NoSuchPath;
-use crate::communication::{SignalKCommunicator, WebSocketError};
+use crate::communication::{PathSubscription, SignalKCommunicator, WebSocketError};
 use crate::dataunits::{
     AngularUnit, DataUnit, DateTimeUnit, DistanceUnit, PositionUnit, PressureUnit, SpeedUnit,
     TemperatureUnit, VoltageUnit,
 };
-use datavalue_derive::DataValue;
+use datavalue_derive::{DataValue, ValueSettings};
 use egui::Ui;
-use signalk::SignalKGetError;
-
-pub trait DataValue {
+use web_time::Duration;
+
+/// How often values are updated unless they ask for something else.
+const DEFAULT_UPDATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Shown instead of a value that hasn't been updated within its max age.
+pub(crate) const STALE_MARKER: &str = "STALE";
+
+/// The value of the first of `paths` that has a fresh one. A stale value is reported over
+/// a path that has no value at all.
+fn first_fresh_f64(
+    communicator: &SignalKCommunicator,
+    paths: &[String],
+    max_age: Duration,
+) -> Result<f64, WebSocketError> {
+    let mut result = Err(WebSocketError::NoSuchPath);
+    for path in paths.iter() {
+        match communicator.get_fresh_f64_for_path(path.clone(), max_age) {
+            Ok(value) => return Ok(value),
+            Err(WebSocketError::Stale) => result = Err(WebSocketError::Stale),
+            Err(error) if result != Err(WebSocketError::Stale) => result = Err(error),
+            Err(_) => {}
+        }
+    }
+    result
+}
+
+fn default_max_age_secs() -> f32 {
+    30.0
+}
+
+/// For values only sent once a minute, so they aren't stale between updates.
+fn slow_max_age_secs() -> f32 {
+    180.0
+}
+
+/// Draw the setting for how old a value may be before it is shown as stale.
+fn add_max_age_config(ui: &mut Ui, max_age_secs: &mut f32) {
+    ui.horizontal(|ui| {
+        ui.label("Max age");
+        ui.add(
+            egui::DragValue::new(max_age_secs)
+                .range(1.0..=3600.0)
+                .suffix(" s"),
+        );
+    });
+}
+
+/// Draw the setting for how often the server should send the value, with a way back to
+/// the value's own default.
+fn add_update_period_config(ui: &mut Ui, update_period_secs: &mut Option<f32>, default: Duration) {
+    ui.horizontal(|ui| {
+        ui.label("Update every");
+        let mut secs = update_period_secs.unwrap_or(default.as_secs_f32());
+        let response = ui.add(
+            egui::DragValue::new(&mut secs)
+                .range(0.1..=600.0)
+                .speed(0.1)
+                .suffix(" s"),
+        );
+        if response.changed() {
+            *update_period_secs = Some(secs);
+        }
+        if update_period_secs.is_some() && ui.button("Default").clicked() {
+            *update_period_secs = None;
+        }
+    });
+}
+
+/// How old a value may be and how often it is sent, the settings every value has.
+pub trait ValueSettings {
+    /// How old the value may be before it is shown as stale.
+    fn max_age(&self) -> Duration;
+    /// How often to ask the server to send the value.
+    fn update_period(&self) -> Duration;
+    /// Draw the settings.
+    fn add_settings_config(&mut self, ui: &mut Ui);
+}
+
+pub trait DataValue: ValueSettings {
     fn name(&self) -> String;
     fn unit_name(&self) -> String;
     fn abbreviation(&self) -> String;
     fn add_config(&mut self, index: usize, ui: &mut Ui);
-    fn fmt_value(&self, communicator: &SignalKCommunicator) -> String;
+    /// The SignalK paths the value is read from, in order of preference.
+    fn paths(&self) -> Vec<String>;
+    /// The current value in SI units, as received from the server, or
+    /// `WebSocketError::Stale` if it is older than the max age.
+    fn raw_value(&self, communicator: &SignalKCommunicator) -> Result<f64, WebSocketError>;
+    /// Format a value in SI units in the selected display unit.
+    fn format_value(&self, value: Result<f64, WebSocketError>) -> String;
+
+    fn fmt_value(&self, communicator: &SignalKCommunicator) -> String {
+        match self.raw_value(communicator) {
+            Err(WebSocketError::Stale) => STALE_MARKER.to_string(),
+            value => self.format_value(value),
+        }
+    }
+
+    /// The updates to ask the server for to show this value.
+    fn subscriptions(&self) -> Vec<PathSubscription> {
+        self.paths()
+            .into_iter()
+            .map(|path| PathSubscription::new(path, self.update_period()))
+            .collect()
+    }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.environment.outside.temperature")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(data_path = "self.environment.outside.temperature", update_period = 60)]
 pub struct AirTemperature {
     name: String,
     abbreviation: String,
     display_unit: TemperatureUnit,
+    #[serde(default = "slow_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for AirTemperature {
@@ -30,16 +133,24 @@ impl Default for AirTemperature {
             name: "Air Temperature".to_string(),
             abbreviation: "AIR".to_string(),
             display_unit: TemperatureUnit::Celsius,
+            max_age_secs: slow_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.wind.angleApparent")]
 pub struct ApparentWindAngle {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for ApparentWindAngle {
@@ -48,16 +159,24 @@ impl Default for ApparentWindAngle {
             name: "Apparent Wind Angle".to_string(),
             abbreviation: "AWA".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.wind.speedApparent")]
 pub struct ApparentWindSpeed {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for ApparentWindSpeed {
@@ -66,16 +185,24 @@ impl Default for ApparentWindSpeed {
             name: "Apparent Wind Speed".to_string(),
             abbreviation: "AWS".to_string(),
             display_unit: SpeedUnit::MeterPerSecond,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.environment.outside.pressure")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(data_path = "self.environment.outside.pressure", update_period = 60)]
 pub struct Barometer {
     name: String,
     abbreviation: String,
     display_unit: PressureUnit,
+    #[serde(default = "slow_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for Barometer {
@@ -84,16 +211,22 @@ impl Default for Barometer {
             name: "Barometer".to_string(),
             abbreviation: "BAR".to_string(),
             display_unit: PressureUnit::HectoPascal,
+            max_age_secs: slow_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, ValueSettings)]
 // #[data_value(data_path = "self.electrical.batteries.house.voltage")]
 pub struct Battery {
     name: String,
     abbreviation: String,
     display_unit: VoltageUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
     path: String,
 }
 
@@ -117,11 +250,19 @@ impl DataValue for Battery {
             ui.text_edit_singleline(path);
         });
         self.display_unit.add_config(index, ui);
+        self.add_settings_config(ui);
     }
 
-    fn fmt_value(&self, communicator: &SignalKCommunicator) -> String {
-        let temp = communicator.get_f64_for_path(self.path.clone());
-        self.display_unit.format(temp)
+    fn paths(&self) -> Vec<String> {
+        vec![self.path.clone()]
+    }
+
+    fn raw_value(&self, communicator: &SignalKCommunicator) -> Result<f64, WebSocketError> {
+        communicator.get_fresh_f64_for_path(self.path.clone(), self.max_age())
+    }
+
+    fn format_value(&self, value: Result<f64, WebSocketError>) -> String {
+        self.display_unit.format(value)
     }
 }
 
@@ -131,17 +272,25 @@ impl Default for Battery {
             name: "Battery".to_string(),
             abbreviation: "BAT".to_string(),
             display_unit: VoltageUnit::Volt,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
             path: "self.electrical.batteries.house.voltage".to_string(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.course.calcValues.bearingTrue")]
 pub struct BearingTrue {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for BearingTrue {
@@ -150,16 +299,24 @@ impl Default for BearingTrue {
             name: "Bearing True".to_string(),
             abbreviation: "BTW".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.trip.log")]
 pub struct DistanceTraveled {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for DistanceTraveled {
@@ -168,15 +325,21 @@ impl Default for DistanceTraveled {
             name: "Distance traveled".to_string(),
             abbreviation: "DIS".to_string(),
             display_unit: DistanceUnit::NauticalMile,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, ValueSettings)]
 pub struct DepthOfWater {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
     paths: Vec<String>,
 }
 impl DataValue for DepthOfWater {
@@ -194,16 +357,22 @@ impl DataValue for DepthOfWater {
 
     fn add_config(&mut self, index: usize, ui: &mut Ui) {
         self.display_unit.add_config(index, ui);
+        self.add_settings_config(ui);
     }
 
-    fn fmt_value(&self, communicator: &SignalKCommunicator) -> String {
-        for path in self.paths.iter() {
-            let value = communicator.get_f64_for_path(path.clone());
-            if let Ok(_) = value {
-                return self.display_unit.format(value);
-            }
+    fn paths(&self) -> Vec<String> {
+        self.paths.clone()
+    }
+
+    fn raw_value(&self, communicator: &SignalKCommunicator) -> Result<f64, WebSocketError> {
+        first_fresh_f64(communicator, &self.paths, self.max_age())
+    }
+
+    fn format_value(&self, value: Result<f64, WebSocketError>) -> String {
+        match value {
+            Ok(value) => self.display_unit.format(Ok(value)),
+            Err(_) => "-----".to_string(),
         }
-        "-----".to_string()
     }
 }
 impl Default for DepthOfWater {
@@ -212,6 +381,8 @@ impl Default for DepthOfWater {
             name: "Depth Of Water".to_string(),
             abbreviation: "DPT".to_string(),
             display_unit: DistanceUnit::Meters,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
             paths: vec![
                 "self.environment.depth.belowSurface".to_string(),
                 "self.environment.depth.belowKeel".to_string(),
@@ -221,12 +392,18 @@ impl Default for DepthOfWater {
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.current.drift")]
 pub struct SpeedOfCurrent {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for SpeedOfCurrent {
@@ -235,16 +412,24 @@ impl Default for SpeedOfCurrent {
             name: "Speed of current".to_string(),
             abbreviation: "DRF".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.course.calcValues.distance")]
 pub struct DistanceToWaypoint {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for DistanceToWaypoint {
@@ -253,16 +438,24 @@ impl Default for DistanceToWaypoint {
             name: "Distance To Waypoint".to_string(),
             abbreviation: "DTW".to_string(),
             display_unit: DistanceUnit::NauticalMile,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.position.altitude")]
 pub struct Altitude {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for Altitude {
@@ -271,16 +464,24 @@ impl Default for Altitude {
             name: "Altitude".to_string(),
             abbreviation: "ELV".to_string(),
             display_unit: DistanceUnit::Meters,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.wind.directionMagnetic")]
 pub struct DirectionOfWindRelativeGround {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for DirectionOfWindRelativeGround {
@@ -289,16 +490,24 @@ impl Default for DirectionOfWindRelativeGround {
             name: "Direction Of Wind Relative Ground".to_string(),
             abbreviation: "GWD".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.headingTrue")]
 pub struct HeadingTrue {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for HeadingTrue {
@@ -307,16 +516,24 @@ impl Default for HeadingTrue {
             name: "The direction the boat points".to_string(),
             abbreviation: "HDG".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.log")]
 pub struct Odometer {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for Odometer {
@@ -325,16 +542,24 @@ impl Default for Odometer {
             name: "Running tally of distance".to_string(),
             abbreviation: "ODO".to_string(),
             display_unit: DistanceUnit::NauticalMile,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.position")]
 pub struct Trip {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for Trip {
@@ -343,16 +568,27 @@ impl Default for Trip {
             name: "A running tally of distance travel since last reset".to_string(),
             abbreviation: "TRP".to_string(),
             display_unit: DistanceUnit::NauticalMile,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.environment.wind.angleTrueGround")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(
+    data_path = "self.environment.wind.angleTrueWater",
+    data_path = "self.environment.wind.angleTrueGround"
+)]
 pub struct TrueWindAngleFromBow {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for crate::datavalues::TrueWindAngleFromBow {
@@ -361,16 +597,24 @@ impl Default for crate::datavalues::TrueWindAngleFromBow {
             name: "True wind angle from bow".to_string(),
             abbreviation: "TWA".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.wind.directionTrue")]
 pub struct TrueWindDirectionRelNorth {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for crate::datavalues::TrueWindDirectionRelNorth {
@@ -379,19 +623,30 @@ impl Default for crate::datavalues::TrueWindDirectionRelNorth {
             name: "True wind direction rel north".to_string(),
             abbreviation: "TWD".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.navigation.log")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(data_path = "self.navigation.position")]
 pub struct Position {
     name: String,
     abbreviation: String,
     display_unit: PositionUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 impl Position {
     pub fn fmt_position(&self, communicator: &SignalKCommunicator) -> String {
+        if communicator.is_stale("self.navigation.position", self.max_age()) {
+            return STALE_MARKER.to_string();
+        }
         if let Some(ref storage) = communicator.signalk_data {
             let sk_data = storage.get();
             if let Some(vessel) = sk_data.get_self() {
@@ -410,16 +665,24 @@ impl Default for Position {
             name: "Current Position".to_string(),
             abbreviation: "POS".to_string(),
             display_unit: PositionUnit::DecimalDegrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.steering.rudderAngle")]
 pub struct RudderAngle {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for crate::datavalues::RudderAngle {
@@ -428,16 +691,24 @@ impl Default for crate::datavalues::RudderAngle {
             name: "Rudder angle".to_string(),
             abbreviation: "RUD".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.environment.wind.speedOverGround")]
 pub struct SpeedOfWindRelativeGround {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for SpeedOfWindRelativeGround {
@@ -446,16 +717,24 @@ impl Default for SpeedOfWindRelativeGround {
             name: "Speed Of Wind Relative Ground".to_string(),
             abbreviation: "GWS".to_string(),
             display_unit: SpeedUnit::MeterPerSecond,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.speedThroughWater")]
 pub struct SpeedThroughWater {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for SpeedThroughWater {
@@ -464,16 +743,24 @@ impl Default for SpeedThroughWater {
             name: "Water Speed".to_string(),
             abbreviation: "STW".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.navigation.speedThroughWater")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(data_path = "self.environment.wind.speedTrue")]
 pub struct TrueWindSpeed {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for TrueWindSpeed {
@@ -482,16 +769,24 @@ impl Default for TrueWindSpeed {
             name: "True wind speed relative vessel".to_string(),
             abbreviation: "TWS".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.speedOverGround")]
 pub struct SpeedOverGround {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for SpeedOverGround {
@@ -500,16 +795,24 @@ impl Default for SpeedOverGround {
             name: "Speed Ground".to_string(),
             abbreviation: "SOG".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.courseOverGroundTrue")]
 pub struct CourseOverGround {
     name: String,
     abbreviation: String,
     display_unit: AngularUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for CourseOverGround {
@@ -518,16 +821,24 @@ impl Default for CourseOverGround {
             name: "Course Over Ground".to_string(),
             abbreviation: "COG".to_string(),
             display_unit: AngularUnit::Degrees,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
-#[data_value(data_path = "self.environment.water.temperature")]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
+#[data_value(data_path = "self.environment.water.temperature", update_period = 60)]
 pub struct WaterTemperature {
     name: String,
     abbreviation: String,
     display_unit: TemperatureUnit,
+    #[serde(default = "slow_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for WaterTemperature {
@@ -536,16 +847,24 @@ impl Default for WaterTemperature {
             name: "Water Temperature".to_string(),
             abbreviation: "SEA".to_string(),
             display_unit: TemperatureUnit::Celsius,
+            max_age_secs: slow_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.course.calcValues.velocityMadeGood")]
 pub struct VelocityMadeGood {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for VelocityMadeGood {
@@ -554,16 +873,24 @@ impl Default for VelocityMadeGood {
             name: "Velocity made good".to_string(),
             abbreviation: "VMG".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.performance.velocityMadeGood")]
 pub struct VelocityMadeGoodUpwind {
     name: String,
     abbreviation: String,
     display_unit: SpeedUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for VelocityMadeGoodUpwind {
@@ -572,16 +899,24 @@ impl Default for VelocityMadeGoodUpwind {
             name: "Velocity made good Upwind".to_string(),
             abbreviation: "WND".to_string(),
             display_unit: SpeedUnit::Knot,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, DataValue)]
+#[derive(
+    Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, DataValue, ValueSettings,
+)]
 #[data_value(data_path = "self.navigation.course.calcValues.crossTrackError")]
 pub struct CrossTrackError {
     name: String,
     abbreviation: String,
     display_unit: DistanceUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl Default for CrossTrackError {
@@ -590,15 +925,21 @@ impl Default for CrossTrackError {
             name: "Cross track error".to_string(),
             abbreviation: "XTE".to_string(),
             display_unit: DistanceUnit::Meters,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, ValueSettings)]
 pub struct UniversalTimeCoordinated {
     name: String,
     abbreviation: String,
     display_unit: DateTimeUnit,
+    #[serde(default = "default_max_age_secs")]
+    max_age_secs: f32,
+    #[serde(default)]
+    update_period_secs: Option<f32>,
 }
 
 impl DataValue for UniversalTimeCoordinated {
@@ -616,9 +957,25 @@ impl DataValue for UniversalTimeCoordinated {
 
     fn add_config(&mut self, index: usize, ui: &mut Ui) {
         self.display_unit.add_config(index, ui);
+        self.add_settings_config(ui);
+    }
+
+    fn paths(&self) -> Vec<String> {
+        vec!["self.navigation.datetime".to_string()]
+    }
+
+    fn raw_value(&self, _communicator: &SignalKCommunicator) -> Result<f64, WebSocketError> {
+        Err(WebSocketError::WrongDataType)
+    }
+
+    fn format_value(&self, value: Result<f64, WebSocketError>) -> String {
+        DataUnit::format(&self.display_unit, value)
     }
 
     fn fmt_value(&self, communicator: &SignalKCommunicator) -> String {
+        if communicator.is_stale("self.navigation.datetime", self.max_age()) {
+            return STALE_MARKER.to_string();
+        }
         if let Some(ref storage) = communicator.signalk_data {
             if let Some(vessel) = storage.get().get_self() {
                 if let Some(ref navigation) = vessel.navigation {
@@ -641,6 +998,8 @@ impl Default for UniversalTimeCoordinated {
             name: "Universal time coordinated".to_string(),
             abbreviation: "UTC".to_string(),
             display_unit: DateTimeUnit::Default,
+            max_age_secs: default_max_age_secs(),
+            update_period_secs: None,
         }
     }
 }
@@ -0,0 +1,83 @@
+use eframe::egui;
+use egui::{FontData, FontDefinitions, FontFamily};
+
+/// Name of the font family used for the big instrument digits.
+const DIGITS_FAMILY: &str = "digits";
+
+const SEVEN_SEGMENT: &str = "digital-7-mono";
+const HACK: &str = "Hack-Bold";
+const FIRA_MONO: &str = "FiraMono-Medium";
+
+/// The fonts that can be used to draw the instrument digits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum DigitFont {
+    #[default]
+    Default,
+    SevenSegment,
+    Hack,
+    FiraMono,
+}
+
+impl DigitFont {
+    pub const ALL: [DigitFont; 4] = [
+        DigitFont::Default,
+        DigitFont::SevenSegment,
+        DigitFont::Hack,
+        DigitFont::FiraMono,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigitFont::Default => "Default",
+            DigitFont::SevenSegment => "LCD",
+            DigitFont::Hack => "Hack",
+            DigitFont::FiraMono => "Fira Mono",
+        }
+    }
+
+    fn font_name(&self) -> Option<&'static str> {
+        match self {
+            DigitFont::Default => None,
+            DigitFont::SevenSegment => Some(SEVEN_SEGMENT),
+            DigitFont::Hack => Some(HACK),
+            DigitFont::FiraMono => Some(FIRA_MONO),
+        }
+    }
+}
+
+/// The font family to use for instrument digits.
+pub fn digits_family() -> FontFamily {
+    FontFamily::Name(DIGITS_FAMILY.into())
+}
+
+/// The egui default fonts plus the bundled ones, with the digits family set to `digit_font`.
+///
+/// The fonts are compiled into the binary so the same definitions work in the native and
+/// the web build.
+pub fn font_definitions(digit_font: DigitFont) -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+    fonts.font_data.insert(
+        SEVEN_SEGMENT.to_owned(),
+        FontData::from_static(include_bytes!("../assets/fonts/digital-7 (mono).ttf")),
+    );
+    fonts.font_data.insert(
+        HACK.to_owned(),
+        FontData::from_static(include_bytes!("../assets/fonts/Hack-Bold.ttf")),
+    );
+    fonts.font_data.insert(
+        FIRA_MONO.to_owned(),
+        FontData::from_static(include_bytes!("../assets/fonts/FiraMono-Medium.ttf")),
+    );
+
+    // Fall back to the monospace fonts for glyphs the digit font lacks, e.g. '°' in LCD.
+    let mut digits = fonts
+        .families
+        .get(&FontFamily::Monospace)
+        .cloned()
+        .unwrap_or_default();
+    if let Some(font_name) = digit_font.font_name() {
+        digits.insert(0, font_name.to_owned());
+    }
+    fonts.families.insert(digits_family(), digits);
+    fonts
+}
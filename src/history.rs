@@ -0,0 +1,105 @@
+use std::collections::{HashMap, VecDeque};
+use web_time::{Duration, Instant};
+
+/// The most samples kept for one path, whatever window and sampling the pages ask for.
+const MAX_SAMPLES: usize = 2000;
+
+/// A value received for a path, in SI units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub time: Instant,
+    pub value: f64,
+}
+
+/// How much history to keep for a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    pub window: Duration,
+    /// Values arriving closer together than this are dropped.
+    pub sample_interval: Duration,
+}
+
+impl Retention {
+    /// The longest window and the shortest interval of the two.
+    fn merge(self, other: Retention) -> Retention {
+        Retention {
+            window: self.window.max(other.window),
+            sample_interval: self.sample_interval.min(other.sample_interval),
+        }
+    }
+
+    /// The sample interval, stretched if needed so the window fits in `MAX_SAMPLES`.
+    fn bounded_interval(&self) -> Duration {
+        self.sample_interval.max(self.window / MAX_SAMPLES as u32)
+    }
+}
+
+struct Series {
+    retention: Retention,
+    samples: VecDeque<Sample>,
+}
+
+impl Series {
+    fn prune(&mut self, now: Instant) {
+        while let Some(first) = self.samples.front() {
+            if now.duration_since(first.time) > self.retention.window
+                || self.samples.len() > MAX_SAMPLES
+            {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Recent values of the paths shown on graph pages, as `Storage` only keeps the latest value.
+///
+/// Only tracked paths are recorded, and each keeps at most `MAX_SAMPLES` samples.
+#[derive(Default)]
+pub struct History {
+    series: HashMap<String, Series>,
+}
+
+impl History {
+    /// Keep history for these paths only. A path asked for more than once keeps the
+    /// longest window with the shortest sample interval.
+    pub fn set_tracked(&mut self, tracked: impl IntoIterator<Item = (String, Retention)>) {
+        let mut wanted: HashMap<String, Retention> = HashMap::new();
+        for (path, retention) in tracked {
+            wanted
+                .entry(path)
+                .and_modify(|existing| *existing = existing.merge(retention))
+                .or_insert(retention);
+        }
+        self.series.retain(|path, _| wanted.contains_key(path));
+        for (path, retention) in wanted {
+            self.series
+                .entry(path)
+                .and_modify(|series| series.retention = retention)
+                .or_insert_with(|| Series {
+                    retention,
+                    samples: VecDeque::new(),
+                });
+        }
+    }
+
+    /// Record a value received for `path`, if the path is tracked.
+    pub fn record(&mut self, path: &str, time: Instant, value: f64) {
+        let Some(series) = self.series.get_mut(path) else {
+            return;
+        };
+        let due = series.samples.back().map_or(true, |last| {
+            time.duration_since(last.time) >= series.retention.bounded_interval()
+        });
+        if due {
+            series.samples.push_back(Sample { time, value });
+        }
+        series.prune(time);
+    }
+
+    /// The recorded samples for `path`, oldest first.
+    pub fn samples(&self, path: &str) -> Option<&VecDeque<Sample>> {
+        self.series.get(path).map(|series| &series.samples)
+    }
+}
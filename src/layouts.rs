@@ -1,16 +1,220 @@
 use eframe::egui;
 use eframe::epaint::text::TextWrapMode;
-use egui::{RichText, Ui};
+use egui::{
+    Align2, Color32, FontFamily, FontId, Painter, Pos2, Rect, RichText, Sense, Shape, Stroke, Ui,
+    UiBuilder, Vec2,
+};
+use std::ops::RangeInclusive;
+use web_time::{Duration, Instant};
 
-use crate::communication::SignalKCommunicator;
+use crate::communication::{PathSubscription, SignalKCommunicator};
 use crate::datatypes::DataValues;
+use crate::datavalues::{
+    ApparentWindAngle, ApparentWindSpeed, BearingTrue, CourseOverGround, DataValue, DepthOfWater,
+    HeadingTrue, SpeedOverGround, SpeedThroughWater, TrueWindAngleFromBow,
+    TrueWindDirectionRelNorth, TrueWindSpeed, STALE_MARKER,
+};
+use crate::fonts::digits_family;
+use crate::history::{Retention, Sample};
+use crate::theme::Palette;
 
 /// The different types of layout that a page can have.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub enum Layout {
     SingleValue(SingleValueLayout),
     DualValues(DualValuesLayout),
-    // TripleValues,
-    // FourValues,
+    TripleValues(TripleValuesLayout),
+    FourValues(FourValuesLayout),
+    Grid(GridLayout),
+    WindDial(WindDialLayout),
+    Heading(HeadingLayout),
+    Graph(GraphLayout),
+}
+
+/// The kinds of layout a new page can be created with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutKind {
+    SingleValue,
+    DualValues,
+    TripleValues,
+    FourValues,
+    Grid,
+    WindDial,
+    Heading,
+    Graph,
+}
+
+impl LayoutKind {
+    pub const ALL: [LayoutKind; 8] = [
+        LayoutKind::SingleValue,
+        LayoutKind::DualValues,
+        LayoutKind::TripleValues,
+        LayoutKind::FourValues,
+        LayoutKind::Grid,
+        LayoutKind::WindDial,
+        LayoutKind::Heading,
+        LayoutKind::Graph,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutKind::SingleValue => "Single Value",
+            LayoutKind::DualValues => "Dual Values",
+            LayoutKind::TripleValues => "Triple Values",
+            LayoutKind::FourValues => "Four Values",
+            LayoutKind::Grid => "Grid",
+            LayoutKind::WindDial => "Wind Dial",
+            LayoutKind::Heading => "Heading",
+            LayoutKind::Graph => "Graph",
+        }
+    }
+}
+
+impl Layout {
+    /// Create a new page of the given kind with default values.
+    pub fn new(kind: LayoutKind, id: usize) -> Self {
+        match kind {
+            LayoutKind::SingleValue => Self::SingleValue(SingleValueLayout::new(
+                id,
+                DataValues::SpeedThroughWater(SpeedThroughWater::default()),
+            )),
+            LayoutKind::DualValues => Self::DualValues(DualValuesLayout::new(
+                id,
+                DataValues::SpeedOverGround(SpeedOverGround::default()),
+                DataValues::CourseOverGround(CourseOverGround::default()),
+            )),
+            LayoutKind::TripleValues => Self::TripleValues(TripleValuesLayout::new(
+                id,
+                [
+                    DataValues::SpeedOverGround(SpeedOverGround::default()),
+                    DataValues::CourseOverGround(CourseOverGround::default()),
+                    DataValues::DepthOfWater(DepthOfWater::default()),
+                ],
+            )),
+            LayoutKind::FourValues => Self::FourValues(FourValuesLayout::new(
+                id,
+                [
+                    DataValues::SpeedThroughWater(SpeedThroughWater::default()),
+                    DataValues::DepthOfWater(DepthOfWater::default()),
+                    DataValues::ApparentWindAngle(ApparentWindAngle::default()),
+                    DataValues::ApparentWindSpeed(ApparentWindSpeed::default()),
+                ],
+            )),
+            LayoutKind::Grid => Self::Grid(GridLayout::new(id)),
+            LayoutKind::WindDial => Self::WindDial(WindDialLayout::new(id)),
+            LayoutKind::Heading => Self::Heading(HeadingLayout::new(id)),
+            LayoutKind::Graph => Self::Graph(GraphLayout::new(
+                id,
+                DataValues::DepthOfWater(DepthOfWater::default()),
+            )),
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        match self {
+            Self::SingleValue(layout) => layout.id,
+            Self::DualValues(layout) => layout.id,
+            Self::TripleValues(layout) => layout.id,
+            Self::FourValues(layout) => layout.id,
+            Self::Grid(layout) => layout.id,
+            Self::WindDial(layout) => layout.id,
+            Self::Heading(layout) => layout.id,
+            Self::Graph(layout) => layout.id,
+        }
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        match self {
+            Self::SingleValue(layout) => layout.id = id,
+            Self::DualValues(layout) => layout.id = id,
+            Self::TripleValues(layout) => layout.id = id,
+            Self::FourValues(layout) => layout.id = id,
+            Self::Grid(layout) => layout.id = id,
+            Self::WindDial(layout) => layout.id = id,
+            Self::Heading(layout) => layout.id = id,
+            Self::Graph(layout) => layout.id = id,
+        }
+    }
+
+    /// How long the page is shown when pages rotate with a per page time.
+    pub fn dwell_secs_mut(&mut self) -> &mut f32 {
+        match self {
+            Self::SingleValue(layout) => &mut layout.dwell_secs,
+            Self::DualValues(layout) => &mut layout.dwell_secs,
+            Self::TripleValues(layout) => &mut layout.dwell_secs,
+            Self::FourValues(layout) => &mut layout.dwell_secs,
+            Self::Grid(layout) => &mut layout.dwell_secs,
+            Self::WindDial(layout) => &mut layout.dwell_secs,
+            Self::Heading(layout) => &mut layout.dwell_secs,
+            Self::Graph(layout) => &mut layout.dwell_secs,
+        }
+    }
+
+    /// The updates the page needs from the server.
+    pub fn subscriptions(&self) -> Vec<PathSubscription> {
+        match self {
+            Self::SingleValue(layout) => layout.value.subscriptions(),
+            Self::DualValues(layout) => [&layout.top_value, &layout.bottom_value]
+                .into_iter()
+                .flat_map(DataValues::subscriptions)
+                .collect(),
+            Self::TripleValues(layout) => layout
+                .values
+                .iter()
+                .flat_map(DataValues::subscriptions)
+                .collect(),
+            Self::FourValues(layout) => layout
+                .values
+                .iter()
+                .flat_map(DataValues::subscriptions)
+                .collect(),
+            Self::Grid(layout) => layout
+                .cells
+                .iter()
+                .flat_map(|cell| cell.value.subscriptions())
+                .collect(),
+            Self::WindDial(layout) => [
+                layout.apparent_angle.subscriptions(),
+                layout.true_angle.subscriptions(),
+                layout.apparent_speed.subscriptions(),
+                layout.true_speed.subscriptions(),
+            ]
+            .concat(),
+            Self::Heading(layout) => [
+                layout.heading.subscriptions(),
+                layout.course.subscriptions(),
+                layout.bearing.subscriptions(),
+                layout.wind_direction.subscriptions(),
+            ]
+            .concat(),
+            Self::Graph(layout) => layout.value.subscriptions(),
+        }
+    }
+
+    /// The values the page needs a history of, and for how long.
+    pub fn history_retention(&self) -> Vec<(String, Retention)> {
+        match self {
+            Self::Graph(layout) => layout.history_retention(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn dwell_secs(&self) -> f32 {
+        match self {
+            Self::SingleValue(layout) => layout.dwell_secs,
+            Self::DualValues(layout) => layout.dwell_secs,
+            Self::TripleValues(layout) => layout.dwell_secs,
+            Self::FourValues(layout) => layout.dwell_secs,
+            Self::Grid(layout) => layout.dwell_secs,
+            Self::WindDial(layout) => layout.dwell_secs,
+            Self::Heading(layout) => layout.dwell_secs,
+            Self::Graph(layout) => layout.dwell_secs,
+        }
+    }
+}
+
+fn default_dwell_secs() -> f32 {
+    3.0
 }
 
 impl LayoutComponent for Layout {
@@ -18,13 +222,25 @@ impl LayoutComponent for Layout {
         match self {
             Self::SingleValue(layout) => layout.add_config(ui),
             Self::DualValues(layout) => layout.add_config(ui),
+            Self::TripleValues(layout) => layout.add_config(ui),
+            Self::FourValues(layout) => layout.add_config(ui),
+            Self::Grid(layout) => layout.add_config(ui),
+            Self::WindDial(layout) => layout.add_config(ui),
+            Self::Heading(layout) => layout.add_config(ui),
+            Self::Graph(layout) => layout.add_config(ui),
         }
     }
 
-    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator) {
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
         match self {
-            Self::SingleValue(layout) => layout.draw_ui(ui, communicator),
-            Self::DualValues(layout) => layout.draw_ui(ui, communicator),
+            Self::SingleValue(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::DualValues(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::TripleValues(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::FourValues(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::Grid(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::WindDial(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::Heading(layout) => layout.draw_ui(ui, communicator, palette),
+            Self::Graph(layout) => layout.draw_ui(ui, communicator, palette),
         }
     }
 }
@@ -35,24 +251,31 @@ pub trait LayoutComponent {
     fn add_config(&mut self, ui: &mut Ui);
 
     /// This will draw the main ui of the component
-    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator);
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette);
 }
 
 /// This is a component that can show a single value on the screen.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct SingleValueLayout {
     id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
     value: DataValues,
 }
 
 impl SingleValueLayout {
     pub fn new(id: usize, value: DataValues) -> Self {
-        Self { id, value }
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            value,
+        }
     }
 }
 
 impl LayoutComponent for SingleValueLayout {
     fn add_config(&mut self, ui: &mut Ui) {
-        let Self { id, value } = self;
+        let Self { id, value, .. } = self;
         ui.label("Single Value Layout");
         egui::ComboBox::new(format!("SingleValueLayout: {}", id), "Value")
             .selected_text(value.abbreviation())
@@ -64,40 +287,33 @@ impl LayoutComponent for SingleValueLayout {
         value.add_config(*id, ui);
     }
 
-    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator) {
-        const SIZE_OF_MAIN_TEXT: f32 = 150.0;
-        const SIZE_OF_ABBREVIATION: f32 = 25.0;
-        const SIZE_OF_UNIT: f32 = 50.0;
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
         const SIZE_OF_FULL_NAME: f32 = 75.0;
-        ui.group(|ui| {
-            ui.spacing_mut().item_spacing.x = 0.0;
-            ui.vertical(|ui| {
-                ui.horizontal(|ui| {
-                    let current_stw = self.value.formatted_value(communicator);
-                    ui.label(
-                        RichText::new(current_stw)
-                            .size(SIZE_OF_MAIN_TEXT)
-                            .monospace(),
-                    );
-                    ui.horizontal(|ui| {
-                        ui.vertical_centered(|ui| {
-                            ui.label(
-                                RichText::new(self.value.abbreviation()).size(SIZE_OF_ABBREVIATION),
-                            );
-                            ui.label(RichText::new(self.value.unit_name()).size(SIZE_OF_UNIT));
-                        });
-                    });
-                });
-                ui.vertical_centered(|ui| {
-                    ui.label(RichText::new(self.value.name()).size(SIZE_OF_FULL_NAME));
-                });
+        let (value_rect, name_rect) = ui
+            .available_rect_before_wrap()
+            .split_top_bottom_at_fraction(0.8);
+        ui.allocate_new_ui(UiBuilder::new().max_rect(value_rect), |ui| {
+            draw_value_cell(ui, &self.value, communicator, palette);
+        });
+        ui.allocate_new_ui(UiBuilder::new().max_rect(name_rect), |ui| {
+            let name = self.value.name();
+            let name_size = measure_text(ui, &name, FontFamily::Proportional, REFERENCE_TEXT_SIZE);
+            let scale = (name_rect.width() / name_size.x).min(name_rect.height() / name_size.y);
+            let size = (REFERENCE_TEXT_SIZE * scale)
+                .floor()
+                .clamp(MIN_TEXT_SIZE, SIZE_OF_FULL_NAME);
+            ui.vertical_centered(|ui| {
+                ui.label(RichText::new(name).size(size).color(palette.label));
             });
         });
     }
 }
 
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct DualValuesLayout {
     id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
     top_value: DataValues,
     bottom_value: DataValues,
 }
@@ -106,6 +322,7 @@ impl DualValuesLayout {
     pub fn new(id: usize, top_value: DataValues, bottom_value: DataValues) -> Self {
         Self {
             id,
+            dwell_secs: default_dwell_secs(),
             top_value,
             bottom_value,
         }
@@ -118,6 +335,7 @@ impl LayoutComponent for DualValuesLayout {
             id,
             top_value,
             bottom_value,
+            ..
         } = self;
         ui.label("Dual Value Layout");
         egui::ComboBox::new(format!("DualValuesLayout_top_{}", id), "Top Value")
@@ -138,47 +356,1219 @@ impl LayoutComponent for DualValuesLayout {
         bottom_value.add_config(*id + 1, ui);
     }
 
-    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator) {
-        const SIZE_OF_MAIN_TEXT: f32 = 150.0;
-        const SIZE_OF_ABBREVIATION: f32 = 25.0;
-        const SIZE_OF_UNIT: f32 = 50.0;
-        ui.group(|ui| {
-            ui.spacing_mut().item_spacing.x = 0.0;
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        let spacing = ui.spacing().item_spacing.y;
+        let grid = CellGrid::new(ui.available_rect_before_wrap(), 2, 1, spacing);
+        let values = [&self.top_value, &self.bottom_value];
+        for (row, value) in values.into_iter().enumerate() {
+            let rect = grid.span_rect(row, 0, 1, 1);
+            ui.allocate_new_ui(UiBuilder::new().max_rect(rect), |ui| {
+                draw_value_cell(ui, value, communicator, palette);
+            });
+        }
+    }
+}
+
+/// A page area split into equally sized rows and columns.
+struct CellGrid {
+    rect: Rect,
+    spacing: f32,
+    cell_size: egui::Vec2,
+}
+
+impl CellGrid {
+    fn new(rect: Rect, rows: usize, columns: usize, spacing: f32) -> Self {
+        let width = (rect.width() - spacing * (columns - 1) as f32) / columns as f32;
+        let height = (rect.height() - spacing * (rows - 1) as f32) / rows as f32;
+        Self {
+            rect,
+            spacing,
+            cell_size: egui::vec2(width, height),
+        }
+    }
+
+    /// The area covered by `row_span` x `column_span` cells starting at `row`, `column`.
+    fn span_rect(&self, row: usize, column: usize, row_span: usize, column_span: usize) -> Rect {
+        let step = self.cell_size + egui::vec2(self.spacing, self.spacing);
+        let min = self.rect.min + egui::vec2(column as f32 * step.x, row as f32 * step.y);
+        let size = egui::vec2(
+            column_span as f32 * step.x - self.spacing,
+            row_span as f32 * step.y - self.spacing,
+        );
+        Rect::from_min_size(min, size)
+    }
+}
+
+/// Font size used to measure text before scaling it to the available space.
+const REFERENCE_TEXT_SIZE: f32 = 100.0;
+/// Never shrink text below this, a clipped value is better than an unreadable one.
+const MIN_TEXT_SIZE: f32 = 8.0;
+
+/// The size of `text` when laid out at `size` in the given font family.
+fn measure_text(ui: &Ui, text: &str, family: FontFamily, size: f32) -> Vec2 {
+    ui.fonts(|fonts| {
+        fonts
+            .layout_no_wrap(text.to_owned(), FontId::new(size, family), Color32::WHITE)
+            .size()
+    })
+}
+
+/// The font size chosen for a value, remembered between frames.
+#[derive(Clone, Copy)]
+struct FittedSize {
+    space: Vec2,
+    font_size: f32,
+}
+
+/// Pick the font size for the value in `ui`, keeping last frame's size as long as the
+/// text still fits and would not grow much, so the digits don't jitter as the value changes.
+fn stable_font_size(ui: &Ui, space: Vec2, fitted: f32) -> f32 {
+    const GROW_THRESHOLD: f32 = 1.25;
+    let id = ui.id().with("fitted_value_size");
+    let previous: Option<FittedSize> = ui.data(|data| data.get_temp(id));
+    let font_size = match previous {
+        Some(previous)
+            if previous.space == space
+                && previous.font_size <= fitted
+                && fitted < previous.font_size * GROW_THRESHOLD =>
+        {
+            previous.font_size
+        }
+        _ => fitted,
+    };
+    ui.data_mut(|data| data.insert_temp(id, FittedSize { space, font_size }));
+    font_size
+}
+
+/// Draw a value with its abbreviation and unit, using the largest text that fits in `ui`.
+fn draw_value_cell(
+    ui: &mut Ui,
+    value: &DataValues,
+    communicator: &SignalKCommunicator,
+    palette: &Palette,
+) {
+    ui.group(|ui| {
+        let space = ui.available_size();
+        ui.set_min_size(space);
+        ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+        let formatted = value.formatted_value(communicator);
+        let abbreviation = value.abbreviation();
+        let unit_name = value.unit_name();
+
+        // The abbreviation and unit are drawn at a sixth and a third of the value size.
+        let value_size = measure_text(ui, &formatted, digits_family(), REFERENCE_TEXT_SIZE);
+        let abbreviation_size = measure_text(
+            ui,
+            &abbreviation,
+            FontFamily::Proportional,
+            REFERENCE_TEXT_SIZE / 6.0,
+        );
+        let unit_size = measure_text(
+            ui,
+            &unit_name,
+            FontFamily::Proportional,
+            REFERENCE_TEXT_SIZE / 3.0,
+        );
+        let needed = egui::vec2(
+            value_size.x + abbreviation_size.x.max(unit_size.x),
+            value_size.y.max(abbreviation_size.y + unit_size.y),
+        );
+        let scale = (space.x / needed.x).min(space.y / needed.y);
+        let fitted = (REFERENCE_TEXT_SIZE * scale).floor().max(MIN_TEXT_SIZE);
+        let size_of_main_text = stable_font_size(ui, space, fitted);
+
+        // The stale marker is dimmed so it doesn't read as a value.
+        let value_color = if formatted == STALE_MARKER {
+            palette.label
+        } else {
+            palette.value
+        };
+        ui.horizontal_centered(|ui| {
+            ui.label(
+                RichText::new(formatted)
+                    .size(size_of_main_text)
+                    .family(digits_family())
+                    .color(value_color),
+            );
             ui.vertical(|ui| {
-                ui.horizontal(|ui| {
-                    let value = self.top_value.formatted_value(communicator);
-                    ui.label(RichText::new(value).size(SIZE_OF_MAIN_TEXT).monospace());
-                    ui.horizontal(|ui| {
-                        ui.vertical_centered(|ui| {
-                            ui.label(
-                                RichText::new(self.top_value.abbreviation())
-                                    .size(SIZE_OF_ABBREVIATION),
-                            );
-                            ui.label(RichText::new(self.top_value.unit_name()).size(SIZE_OF_UNIT));
-                        });
-                    });
-                });
+                ui.label(
+                    RichText::new(abbreviation)
+                        .size(size_of_main_text / 6.0)
+                        .color(palette.label),
+                );
+                ui.label(
+                    RichText::new(unit_name)
+                        .size(size_of_main_text / 3.0)
+                        .color(palette.label),
+                );
             });
         });
-        ui.group(|ui| {
-            ui.spacing_mut().item_spacing.x = 0.0;
-            ui.vertical(|ui| {
-                ui.horizontal(|ui| {
-                    let value = self.bottom_value.formatted_value(communicator);
-                    ui.label(RichText::new(value).size(SIZE_OF_MAIN_TEXT).monospace());
-                    ui.horizontal(|ui| {
-                        ui.vertical_centered(|ui| {
-                            ui.label(
-                                RichText::new(self.bottom_value.abbreviation())
-                                    .size(SIZE_OF_ABBREVIATION),
-                            );
-                            ui.label(
-                                RichText::new(self.bottom_value.unit_name()).size(SIZE_OF_UNIT),
-                            );
-                        });
-                    });
-                });
+    });
+}
+
+/// Draw each value in its own cell of a `rows` x `columns` grid filling `ui`.
+fn draw_value_grid(
+    ui: &mut Ui,
+    values: &[DataValues],
+    rows: usize,
+    columns: usize,
+    communicator: &SignalKCommunicator,
+    palette: &Palette,
+) {
+    let spacing = ui.spacing().item_spacing.y;
+    let grid = CellGrid::new(ui.available_rect_before_wrap(), rows, columns, spacing);
+    for (index, value) in values.iter().enumerate() {
+        let cell = grid.span_rect(index / columns, index % columns, 1, 1);
+        ui.allocate_new_ui(UiBuilder::new().max_rect(cell), |ui| {
+            draw_value_cell(ui, value, communicator, palette);
+        });
+    }
+}
+
+/// Draw the selection of value and unit for one of the values on a page.
+fn add_value_config(
+    ui: &mut Ui,
+    id_salt: String,
+    label: &str,
+    value: &mut DataValues,
+    index: usize,
+) {
+    egui::ComboBox::new(id_salt, label)
+        .selected_text(value.abbreviation())
+        .show_ui(ui, |ui| {
+            ui.style_mut().wrap_mode = Some(TextWrapMode::Truncate);
+            ui.set_min_width(60.0);
+            value.add_config_values(ui);
+        });
+    value.add_config(index, ui);
+}
+
+/// Three values stacked on top of each other.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct TripleValuesLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    values: [DataValues; 3],
+}
+
+impl TripleValuesLayout {
+    const LABELS: [&'static str; 3] = ["Top Value", "Middle Value", "Bottom Value"];
+
+    pub fn new(id: usize, values: [DataValues; 3]) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            values,
+        }
+    }
+}
+
+impl LayoutComponent for TripleValuesLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        let Self { id, values, .. } = self;
+        ui.label("Triple Value Layout");
+        for (index, (value, label)) in values.iter_mut().zip(Self::LABELS).enumerate() {
+            add_value_config(
+                ui,
+                format!("TripleValuesLayout_{}_{}", index, id),
+                label,
+                value,
+                *id + index,
+            );
+        }
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        draw_value_grid(ui, &self.values, 3, 1, communicator, palette);
+    }
+}
+
+/// Four values in a two by two grid.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct FourValuesLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    values: [DataValues; 4],
+}
+
+impl FourValuesLayout {
+    const LABELS: [&'static str; 4] = [
+        "Top Left Value",
+        "Top Right Value",
+        "Bottom Left Value",
+        "Bottom Right Value",
+    ];
+
+    pub fn new(id: usize, values: [DataValues; 4]) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            values,
+        }
+    }
+}
+
+impl LayoutComponent for FourValuesLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        let Self { id, values, .. } = self;
+        ui.label("Four Value Layout");
+        for (index, (value, label)) in values.iter_mut().zip(Self::LABELS).enumerate() {
+            add_value_config(
+                ui,
+                format!("FourValuesLayout_{}_{}", index, id),
+                label,
+                value,
+                *id + index,
+            );
+        }
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        draw_value_grid(ui, &self.values, 2, 2, communicator, palette);
+    }
+}
+
+/// One value on a grid page, placed at `row`, `column` and covering the spanned cells.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct GridCell {
+    row: usize,
+    column: usize,
+    row_span: usize,
+    column_span: usize,
+    value: DataValues,
+}
+
+impl GridCell {
+    pub fn new(row: usize, column: usize, value: DataValues) -> Self {
+        Self {
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+            value,
+        }
+    }
+
+    pub fn with_span(mut self, row_span: usize, column_span: usize) -> Self {
+        self.row_span = row_span;
+        self.column_span = column_span;
+        self
+    }
+
+    /// Move and shrink the cell so it is inside a grid of this size.
+    fn fit(&mut self, rows: usize, columns: usize) {
+        self.row = self.row.min(rows - 1);
+        self.column = self.column.min(columns - 1);
+        self.row_span = self.row_span.clamp(1, rows - self.row);
+        self.column_span = self.column_span.clamp(1, columns - self.column);
+    }
+
+    fn covers(&self, row: usize, column: usize) -> bool {
+        (self.row..self.row + self.row_span).contains(&row)
+            && (self.column..self.column + self.column_span).contains(&column)
+    }
+
+    fn overlaps(&self, other: &GridCell) -> bool {
+        self.row < other.row + other.row_span
+            && other.row < self.row + self.row_span
+            && self.column < other.column + other.column_span
+            && other.column < self.column + self.column_span
+    }
+}
+
+/// A page split into a user selected number of rows and columns, where each value
+/// can span several cells.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct GridLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    #[serde(deserialize_with = "deserialize_grid_size")]
+    rows: usize,
+    #[serde(deserialize_with = "deserialize_grid_size")]
+    columns: usize,
+    cells: Vec<GridCell>,
+}
+
+/// A grid has at least one row and column, whatever was saved.
+fn deserialize_grid_size<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let size = <usize as serde::Deserialize>::deserialize(deserializer)?;
+    Ok(size.max(1))
+}
+
+impl GridLayout {
+    const MAX_ROWS: usize = 6;
+    const MAX_COLUMNS: usize = 6;
+
+    /// A big water speed across the top with four smaller values below.
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            rows: 3,
+            columns: 2,
+            cells: vec![
+                GridCell::new(
+                    0,
+                    0,
+                    DataValues::SpeedThroughWater(SpeedThroughWater::default()),
+                )
+                .with_span(1, 2),
+                GridCell::new(
+                    1,
+                    0,
+                    DataValues::SpeedOverGround(SpeedOverGround::default()),
+                ),
+                GridCell::new(
+                    1,
+                    1,
+                    DataValues::CourseOverGround(CourseOverGround::default()),
+                ),
+                GridCell::new(2, 0, DataValues::DepthOfWater(DepthOfWater::default())),
+                GridCell::new(
+                    2,
+                    1,
+                    DataValues::ApparentWindSpeed(ApparentWindSpeed::default()),
+                ),
+            ],
+        }
+    }
+
+    /// The first cell position not covered by any cell, if the grid has room left.
+    fn free_position(&self) -> Option<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+            .find(|(row, column)| !self.cells.iter().any(|cell| cell.covers(*row, *column)))
+    }
+
+    /// If the cell at `index` shares a grid position with another cell.
+    fn overlapped(&self, index: usize) -> bool {
+        let cell = &self.cells[index];
+        self.cells
+            .iter()
+            .enumerate()
+            .any(|(other_index, other)| other_index != index && cell.overlaps(other))
+    }
+}
+
+impl LayoutComponent for GridLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        ui.label("Grid Layout");
+        ui.horizontal(|ui| {
+            ui.label("Rows");
+            ui.add(egui::DragValue::new(&mut self.rows).range(1..=Self::MAX_ROWS));
+            ui.label("Columns");
+            ui.add(egui::DragValue::new(&mut self.columns).range(1..=Self::MAX_COLUMNS));
+        });
+        // Cells left outside when the grid shrinks are moved back in, not dropped.
+        for cell in self.cells.iter_mut() {
+            cell.fit(self.rows, self.columns);
+        }
+        let overlapped = (0..self.cells.len())
+            .map(|index| self.overlapped(index))
+            .collect::<Vec<_>>();
+        let Self {
+            id,
+            rows,
+            columns,
+            cells,
+            ..
+        } = self;
+        let mut remove = None;
+        for (index, cell) in cells.iter_mut().enumerate() {
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Row");
+                ui.add(egui::DragValue::new(&mut cell.row).range(0..=*rows - 1));
+                ui.label("Column");
+                ui.add(egui::DragValue::new(&mut cell.column).range(0..=*columns - 1));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Span rows");
+                ui.add(egui::DragValue::new(&mut cell.row_span).range(1..=*rows));
+                ui.label("Span columns");
+                ui.add(egui::DragValue::new(&mut cell.column_span).range(1..=*columns));
+            });
+            if overlapped[index] {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    "Overlaps another value, only one of them is shown in full",
+                );
+            }
+            ui.horizontal(|ui| {
+                add_value_config(
+                    ui,
+                    format!("GridLayout_{}_{}", index, id),
+                    "Value",
+                    &mut cell.value,
+                    *id + index,
+                );
+                if ui.button("Remove").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if let Some(index) = remove {
+            cells.remove(index);
+        }
+        ui.separator();
+        let free_position = self.free_position();
+        if ui
+            .add_enabled(free_position.is_some(), egui::Button::new("Add value"))
+            .clicked()
+        {
+            if let Some((row, column)) = free_position {
+                self.cells.push(GridCell::new(
+                    row,
+                    column,
+                    DataValues::SpeedThroughWater(SpeedThroughWater::default()),
+                ));
+            }
+        }
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        let spacing = ui.spacing().item_spacing.y;
+        let grid = CellGrid::new(
+            ui.available_rect_before_wrap(),
+            self.rows,
+            self.columns,
+            spacing,
+        );
+        for cell in &self.cells {
+            let mut cell = cell.clone();
+            cell.fit(self.rows, self.columns);
+            let rect = grid.span_rect(cell.row, cell.column, cell.row_span, cell.column_span);
+            ui.allocate_new_ui(UiBuilder::new().max_rect(rect), |ui| {
+                draw_value_cell(ui, &cell.value, communicator, palette);
             });
+        }
+    }
+}
+
+/// An analog wind instrument, apparent and true wind angle on a dial around the wind speeds.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct WindDialLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    /// Outer edge of the red and green close hauled sectors, in degrees off the bow.
+    close_hauled_degrees: f32,
+    apparent_angle: ApparentWindAngle,
+    true_angle: TrueWindAngleFromBow,
+    apparent_speed: ApparentWindSpeed,
+    true_speed: TrueWindSpeed,
+}
+
+impl WindDialLayout {
+    /// Inner edge of the close hauled sectors, in degrees off the bow.
+    const SECTOR_START_DEGREES: f32 = 20.0;
+
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            close_hauled_degrees: 60.0,
+            apparent_angle: ApparentWindAngle::default(),
+            true_angle: TrueWindAngleFromBow::default(),
+            apparent_speed: ApparentWindSpeed::default(),
+            true_speed: TrueWindSpeed::default(),
+        }
+    }
+}
+
+impl LayoutComponent for WindDialLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        let Self {
+            close_hauled_degrees,
+            apparent_speed,
+            true_speed,
+            ..
+        } = self;
+        ui.label("Wind Dial Layout");
+        ui.horizontal(|ui| {
+            ui.label("Close hauled sector to");
+            ui.add(
+                egui::DragValue::new(close_hauled_degrees)
+                    .range(Self::SECTOR_START_DEGREES..=90.0)
+                    .suffix("°"),
+            );
         });
+        ui.label(apparent_speed.abbreviation());
+        apparent_speed.add_config(0, ui);
+        ui.label(true_speed.abbreviation());
+        true_speed.add_config(1, ui);
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        let (rect, _) = ui.allocate_exact_size(ui.available_size(), Sense::hover());
+        let painter = ui.painter_at(rect);
+        let center = rect.center();
+        let radius = 0.45 * rect.width().min(rect.height());
+        let line_width = (radius / 80.0).max(1.0);
+
+        let sector_radius = 0.9 * radius;
+        let sector_stroke = 0.08 * radius;
+        let start = Self::SECTOR_START_DEGREES.to_radians();
+        let end = self.close_hauled_degrees.to_radians();
+        draw_arc(
+            &painter,
+            center,
+            sector_radius,
+            start..=end,
+            Stroke::new(sector_stroke, palette.starboard),
+        );
+        draw_arc(
+            &painter,
+            center,
+            sector_radius,
+            -end..=-start,
+            Stroke::new(sector_stroke, palette.port),
+        );
+
+        painter.circle_stroke(center, radius, Stroke::new(line_width, palette.frame));
+        for degrees in (0..360).step_by(10) {
+            let angle = (degrees as f32).to_radians();
+            let inner = if degrees % 30 == 0 { 0.8 } else { 0.86 } * radius;
+            painter.line_segment(
+                [
+                    dial_point(center, inner, angle),
+                    dial_point(center, radius, angle),
+                ],
+                Stroke::new(line_width, palette.frame),
+            );
+            if degrees % 30 == 0 && degrees != 0 {
+                // Wind angles are read off the bow, 0 to 180 on either side.
+                let label = if degrees > 180 {
+                    360 - degrees
+                } else {
+                    degrees
+                };
+                painter.text(
+                    dial_point(center, 0.7 * radius, angle),
+                    Align2::CENTER_CENTER,
+                    label.to_string(),
+                    FontId::proportional(0.08 * radius),
+                    palette.label,
+                );
+            }
+        }
+
+        // The bow marker sits on the rim, pointing ahead.
+        let bow_size = 0.07 * radius;
+        painter.add(Shape::convex_polygon(
+            vec![
+                center + egui::vec2(0.0, -radius - bow_size),
+                center + egui::vec2(bow_size, -radius + bow_size),
+                center + egui::vec2(-bow_size, -radius + bow_size),
+            ],
+            palette.value,
+            Stroke::NONE,
+        ));
+
+        if let Ok(angle) = self.true_angle.raw_value(communicator) {
+            draw_needle(&painter, center, radius, angle as f32, 0.04, palette.label);
+        }
+        if let Ok(angle) = self.apparent_angle.raw_value(communicator) {
+            draw_needle(&painter, center, radius, angle as f32, 0.06, palette.accent);
+        }
+
+        let speeds = [
+            (&self.apparent_speed as &dyn DataValue, -0.32),
+            (&self.true_speed as &dyn DataValue, 0.08),
+        ];
+        for (speed, offset) in speeds {
+            let top = center + egui::vec2(0.0, offset * radius);
+            painter.text(
+                top,
+                Align2::CENTER_TOP,
+                format!("{} {}", speed.abbreviation(), speed.unit_name()),
+                FontId::proportional(0.08 * radius),
+                palette.label,
+            );
+            painter.text(
+                top + egui::vec2(0.0, 0.08 * radius),
+                Align2::CENTER_TOP,
+                speed.fmt_value(communicator).trim(),
+                FontId::new(0.2 * radius, digits_family()),
+                palette.value,
+            );
+        }
+    }
+}
+
+/// A point on a dial, `angle` radians clockwise from straight up.
+fn dial_point(center: Pos2, radius: f32, angle: f32) -> Pos2 {
+    center + radius * egui::vec2(angle.sin(), -angle.cos())
+}
+
+/// Draw part of a circle between two dial angles.
+fn draw_arc(
+    painter: &Painter,
+    center: Pos2,
+    radius: f32,
+    angles: RangeInclusive<f32>,
+    stroke: Stroke,
+) {
+    const SEGMENTS: usize = 32;
+    let (start, end) = angles.into_inner();
+    let points = (0..=SEGMENTS)
+        .map(|i| {
+            dial_point(
+                center,
+                radius,
+                start + (end - start) * i as f32 / SEGMENTS as f32,
+            )
+        })
+        .collect();
+    painter.add(Shape::line(points, stroke));
+}
+
+/// Draw a needle along the outer part of a dial, leaving the centre free for digits.
+/// `width` is the width of the needle base as a fraction of the radius.
+fn draw_needle(
+    painter: &Painter,
+    center: Pos2,
+    radius: f32,
+    angle: f32,
+    width: f32,
+    color: Color32,
+) {
+    let base = dial_point(center, 0.5 * radius, angle);
+    let across = width * radius * egui::vec2(angle.cos(), angle.sin());
+    painter.add(Shape::convex_polygon(
+        vec![
+            dial_point(center, 0.88 * radius, angle),
+            base + across,
+            base - across,
+        ],
+        color,
+        Stroke::NONE,
+    ));
+}
+
+/// How the heading page shows the heading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum HeadingStyle {
+    /// A compass card turning under a fixed lubber line.
+    #[default]
+    Card,
+    /// A horizontal strip of the compass around the heading.
+    Tape,
+}
+
+impl HeadingStyle {
+    pub const ALL: [HeadingStyle; 2] = [HeadingStyle::Card, HeadingStyle::Tape];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HeadingStyle::Card => "Compass card",
+            HeadingStyle::Tape => "Heading tape",
+        }
+    }
+}
+
+/// The true heading with markers for course over ground, bearing to waypoint and wind direction.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct HeadingLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    style: HeadingStyle,
+    heading: HeadingTrue,
+    course: CourseOverGround,
+    bearing: BearingTrue,
+    wind_direction: TrueWindDirectionRelNorth,
+}
+
+/// A direction marked on the heading page, in degrees true.
+struct HeadingMarker {
+    abbreviation: String,
+    degrees: f32,
+    color: Color32,
+}
+
+impl HeadingLayout {
+    /// The part of the compass visible on the tape, in degrees.
+    const TAPE_SPAN_DEGREES: f32 = 90.0;
+
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            style: HeadingStyle::default(),
+            heading: HeadingTrue::default(),
+            course: CourseOverGround::default(),
+            bearing: BearingTrue::default(),
+            wind_direction: TrueWindDirectionRelNorth::default(),
+        }
+    }
+
+    /// The markers for the paths that currently have values.
+    fn markers(&self, communicator: &SignalKCommunicator, palette: &Palette) -> Vec<HeadingMarker> {
+        [
+            (&self.course as &dyn DataValue, palette.accent),
+            (&self.bearing as &dyn DataValue, palette.starboard),
+            (&self.wind_direction as &dyn DataValue, palette.port),
+        ]
+        .into_iter()
+        .filter_map(|(value, color)| {
+            let radians = value.raw_value(communicator).ok()?;
+            Some(HeadingMarker {
+                abbreviation: value.abbreviation(),
+                degrees: (radians as f32).to_degrees(),
+                color,
+            })
+        })
+        .collect()
+    }
+
+    fn draw_card(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        heading: f32,
+        heading_text: &str,
+        markers: &[HeadingMarker],
+        palette: &Palette,
+    ) {
+        let center = rect.center();
+        let radius = 0.42 * rect.width().min(rect.height());
+        let line_width = (radius / 80.0).max(1.0);
+        let stroke = Stroke::new(line_width, palette.frame);
+        // The card turns so the heading is always at the top.
+        let card_angle = |degrees: f32| (degrees - heading).to_radians();
+
+        painter.circle_stroke(center, radius, stroke);
+        for degrees in (0..360).step_by(5) {
+            let length = match degrees {
+                d if d % 30 == 0 => 0.12,
+                d if d % 10 == 0 => 0.07,
+                _ => 0.04,
+            };
+            let angle = card_angle(degrees as f32);
+            painter.line_segment(
+                [
+                    dial_point(center, (1.0 - length) * radius, angle),
+                    dial_point(center, radius, angle),
+                ],
+                stroke,
+            );
+            if degrees % 30 == 0 {
+                painter.text(
+                    dial_point(center, 0.76 * radius, angle),
+                    Align2::CENTER_CENTER,
+                    compass_label(degrees),
+                    FontId::proportional(0.09 * radius),
+                    palette.label,
+                );
+            }
+        }
+
+        // The lubber line marks the heading at the top of the card.
+        let lubber_size = 0.07 * radius;
+        painter.add(Shape::convex_polygon(
+            vec![
+                center + egui::vec2(0.0, -radius + lubber_size),
+                center + egui::vec2(lubber_size, -radius - lubber_size),
+                center + egui::vec2(-lubber_size, -radius - lubber_size),
+            ],
+            palette.value,
+            Stroke::NONE,
+        ));
+
+        for marker in markers {
+            let angle = card_angle(marker.degrees);
+            let base = dial_point(center, 0.98 * radius, angle);
+            let across = 0.05 * radius * egui::vec2(angle.cos(), angle.sin());
+            painter.add(Shape::convex_polygon(
+                vec![
+                    dial_point(center, 0.84 * radius, angle),
+                    base + across,
+                    base - across,
+                ],
+                marker.color,
+                Stroke::NONE,
+            ));
+            painter.text(
+                dial_point(center, 1.12 * radius, angle),
+                Align2::CENTER_CENTER,
+                &marker.abbreviation,
+                FontId::proportional(0.07 * radius),
+                marker.color,
+            );
+        }
+
+        self.draw_heading_digits(painter, center, 0.2 * radius, heading_text, palette);
+    }
+
+    fn draw_tape(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        heading: f32,
+        heading_text: &str,
+        markers: &[HeadingMarker],
+        palette: &Palette,
+    ) {
+        let (digits_rect, rest) = rect.split_top_bottom_at_fraction(0.45);
+        let (tape_rect, labels_rect) = rest.split_top_bottom_at_fraction(0.65);
+        let center_x = tape_rect.center().x;
+        let pixels_per_degree = tape_rect.width() / Self::TAPE_SPAN_DEGREES;
+        let line_width = (tape_rect.height() / 60.0).max(1.0);
+        let stroke = Stroke::new(line_width, palette.frame);
+        let half_span = Self::TAPE_SPAN_DEGREES / 2.0;
+
+        painter.rect_stroke(tape_rect, 0.0, stroke);
+        let first = ((heading - half_span) / 5.0).ceil() as i32 * 5;
+        let last = ((heading + half_span) / 5.0).floor() as i32 * 5;
+        for degrees in (first..=last).step_by(5) {
+            let x = center_x + (degrees as f32 - heading) * pixels_per_degree;
+            let length = if degrees % 10 == 0 { 0.3 } else { 0.15 };
+            painter.line_segment(
+                [
+                    egui::pos2(x, tape_rect.top()),
+                    egui::pos2(x, tape_rect.top() + length * tape_rect.height()),
+                ],
+                stroke,
+            );
+            if degrees % 10 == 0 {
+                painter.text(
+                    egui::pos2(x, tape_rect.top() + 0.35 * tape_rect.height()),
+                    Align2::CENTER_TOP,
+                    compass_label(degrees.rem_euclid(360)),
+                    FontId::proportional(0.3 * tape_rect.height()),
+                    palette.label,
+                );
+            }
+        }
+
+        painter.line_segment(
+            [
+                egui::pos2(center_x, tape_rect.top()),
+                egui::pos2(center_x, tape_rect.bottom()),
+            ],
+            Stroke::new(2.0 * line_width, palette.value),
+        );
+
+        // Markers outside the tape are held at the edge, so the direction to turn still shows.
+        let marker_size = 0.12 * tape_rect.height();
+        for marker in markers {
+            let offset = wrap_degrees(marker.degrees - heading).clamp(-half_span, half_span);
+            let x = center_x + offset * pixels_per_degree;
+            painter.add(Shape::convex_polygon(
+                vec![
+                    egui::pos2(x, tape_rect.bottom() - 1.5 * marker_size),
+                    egui::pos2(x + marker_size, tape_rect.bottom()),
+                    egui::pos2(x - marker_size, tape_rect.bottom()),
+                ],
+                marker.color,
+                Stroke::NONE,
+            ));
+            painter.text(
+                egui::pos2(x, labels_rect.top()),
+                Align2::CENTER_TOP,
+                &marker.abbreviation,
+                FontId::proportional(0.6 * labels_rect.height()),
+                marker.color,
+            );
+        }
+
+        let digits_size = (0.7 * digits_rect.height()).min(0.2 * digits_rect.width());
+        self.draw_heading_digits(
+            painter,
+            digits_rect.center() - egui::vec2(0.0, 0.5 * digits_size),
+            digits_size,
+            heading_text,
+            palette,
+        );
+    }
+
+    /// Draw the heading with its abbreviation and unit, the digits `size` high below `top`.
+    fn draw_heading_digits(
+        &self,
+        painter: &Painter,
+        top: Pos2,
+        size: f32,
+        heading_text: &str,
+        palette: &Palette,
+    ) {
+        painter.text(
+            top - egui::vec2(0.0, 0.05 * size),
+            Align2::CENTER_BOTTOM,
+            format!(
+                "{} {}",
+                self.heading.abbreviation(),
+                self.heading.unit_name()
+            ),
+            FontId::proportional(0.35 * size),
+            palette.label,
+        );
+        painter.text(
+            top,
+            Align2::CENTER_TOP,
+            heading_text.trim(),
+            FontId::new(size, digits_family()),
+            palette.value,
+        );
+    }
+}
+
+impl LayoutComponent for HeadingLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        let Self { style, heading, .. } = self;
+        ui.label("Heading Layout");
+        egui::ComboBox::new("heading_style", "Style")
+            .selected_text(style.name())
+            .show_ui(ui, |ui| {
+                for option in HeadingStyle::ALL {
+                    ui.selectable_value(style, option, option.name());
+                }
+            });
+        heading.add_config(0, ui);
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        let (rect, _) = ui.allocate_exact_size(ui.available_size(), Sense::hover());
+        let painter = ui.painter_at(rect);
+        // Without a heading the card stays north up and only the markers are shown.
+        let heading = self
+            .heading
+            .raw_value(communicator)
+            .map(|radians| (radians as f32).to_degrees())
+            .unwrap_or_default();
+        let heading_text = self.heading.fmt_value(communicator);
+        let markers = self.markers(communicator, palette);
+        match self.style {
+            HeadingStyle::Card => {
+                self.draw_card(&painter, rect, heading, &heading_text, &markers, palette)
+            }
+            HeadingStyle::Tape => {
+                self.draw_tape(&painter, rect, heading, &heading_text, &markers, palette)
+            }
+        }
+    }
+}
+
+/// The label for a compass direction, a letter for the cardinal points and degrees otherwise.
+fn compass_label(degrees: i32) -> String {
+    match degrees {
+        0 => "N".to_string(),
+        90 => "E".to_string(),
+        180 => "S".to_string(),
+        270 => "W".to_string(),
+        _ => degrees.to_string(),
+    }
+}
+
+/// An angle difference in degrees, wrapped to -180..180.
+fn wrap_degrees(degrees: f32) -> f32 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// A value with a line chart of its recent history below it.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct GraphLayout {
+    id: usize,
+    #[serde(default = "default_dwell_secs")]
+    dwell_secs: f32,
+    value: DataValues,
+    /// How far back the chart goes.
+    window_minutes: f32,
+    /// Shortest time between two recorded values.
+    sample_secs: f32,
+}
+
+impl GraphLayout {
+    pub fn new(id: usize, value: DataValues) -> Self {
+        Self {
+            id,
+            dwell_secs: default_dwell_secs(),
+            value,
+            window_minutes: 10.0,
+            sample_secs: 5.0,
+        }
+    }
+
+    fn retention(&self) -> Retention {
+        Retention {
+            window: Duration::from_secs_f32(self.window_minutes * 60.0),
+            sample_interval: Duration::from_secs_f32(self.sample_secs),
+        }
+    }
+
+    fn history_retention(&self) -> Vec<(String, Retention)> {
+        let retention = self.retention();
+        self.value
+            .paths()
+            .into_iter()
+            .map(|path| (path, retention))
+            .collect()
+    }
+
+    fn draw_chart(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        communicator: &SignalKCommunicator,
+        palette: &Palette,
+    ) {
+        let line_width = (rect.height() / 150.0).max(1.0);
+        let label_font = FontId::proportional((rect.height() / 10.0).clamp(10.0, 24.0));
+        let margin = 0.5 * label_font.size;
+        painter.rect_stroke(rect, 0.0, Stroke::new(line_width, palette.frame));
+        painter.text(
+            rect.right_top() + egui::vec2(-margin, margin),
+            Align2::RIGHT_TOP,
+            format!("{} min", self.window_minutes),
+            label_font.clone(),
+            palette.label,
+        );
+
+        // Use the first path with history, the same preference as for the current value.
+        let window = self.retention().window;
+        let now = Instant::now();
+        let samples: Vec<Sample> = self
+            .value
+            .paths()
+            .iter()
+            .filter_map(|path| communicator.history.samples(path))
+            .find(|samples| !samples.is_empty())
+            .map(|samples| {
+                samples
+                    .iter()
+                    .filter(|sample| now.duration_since(sample.time) <= window)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default();
+        if samples.is_empty() {
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                "No history yet",
+                label_font,
+                palette.label,
+            );
+            return;
+        }
+
+        let (min, max) = samples
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), sample| {
+                (min.min(sample.value), max.max(sample.value))
+            });
+        // Leave some room above and below the line, also when the value is constant.
+        let padding = ((max - min) * 0.1)
+            .max(min.abs().max(max.abs()) * 0.01)
+            .max(1e-3);
+        let (low, high) = (min - padding, max + padding);
+        let to_screen = |sample: &Sample| {
+            let age = now.duration_since(sample.time).as_secs_f32() / window.as_secs_f32();
+            let height = ((sample.value - low) / (high - low)) as f32;
+            egui::pos2(
+                rect.right() - age * rect.width(),
+                rect.bottom() - height * rect.height(),
+            )
+        };
+        let points: Vec<Pos2> = samples.iter().map(to_screen).collect();
+        painter.add(Shape::line(
+            points,
+            Stroke::new(2.0 * line_width, palette.value),
+        ));
+
+        let unit_name = self.value.unit_name();
+        for (value, position, align) in [
+            (
+                max,
+                rect.left_top() + egui::vec2(margin, margin),
+                Align2::LEFT_TOP,
+            ),
+            (
+                min,
+                rect.left_bottom() + egui::vec2(margin, -margin),
+                Align2::LEFT_BOTTOM,
+            ),
+        ] {
+            painter.text(
+                position,
+                align,
+                format!(
+                    "{} {}",
+                    self.value.format_value(Ok(value)).trim(),
+                    unit_name
+                ),
+                label_font.clone(),
+                palette.label,
+            );
+        }
+    }
+}
+
+impl LayoutComponent for GraphLayout {
+    fn add_config(&mut self, ui: &mut Ui) {
+        let Self {
+            id,
+            value,
+            window_minutes,
+            sample_secs,
+            ..
+        } = self;
+        ui.label("Graph Layout");
+        add_value_config(ui, format!("GraphLayout_{}", id), "Value", value, *id);
+        ui.horizontal(|ui| {
+            ui.label("Window");
+            ui.add(
+                egui::DragValue::new(window_minutes)
+                    .range(1.0..=1440.0)
+                    .suffix(" min"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Sample every");
+            ui.add(
+                egui::DragValue::new(sample_secs)
+                    .range(1.0..=600.0)
+                    .suffix(" s"),
+            );
+        });
+    }
+
+    fn draw_ui(&self, ui: &mut Ui, communicator: &SignalKCommunicator, palette: &Palette) {
+        let (value_rect, chart_rect) = ui
+            .available_rect_before_wrap()
+            .split_top_bottom_at_fraction(0.4);
+        ui.allocate_new_ui(UiBuilder::new().max_rect(value_rect), |ui| {
+            draw_value_cell(ui, &self.value, communicator, palette);
+        });
+        let chart_rect = chart_rect.shrink(ui.spacing().item_spacing.y);
+        let (rect, _) = ui.allocate_exact_size(chart_rect.size(), Sense::hover());
+        self.draw_chart(&ui.painter_at(rect), rect, communicator, palette);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_empty_grids_as_one_cell() {
+        let mut layout = serde_json::to_value(GridLayout::new(0)).unwrap();
+        layout["rows"] = 0.into();
+        layout["columns"] = 0.into();
+        let mut layout: GridLayout = serde_json::from_value(layout).unwrap();
+        assert_eq!((layout.rows, layout.columns), (1, 1));
+        for cell in layout.cells.iter_mut() {
+            cell.fit(layout.rows, layout.columns);
+            assert_eq!(
+                (cell.row, cell.column, cell.row_span, cell.column_span),
+                (0, 0, 1, 1)
+            );
+        }
     }
 }
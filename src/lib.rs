@@ -1,10 +1,22 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+mod auth;
 mod communication;
+mod datasource;
 mod datatypes;
 mod dataunits;
 mod datavalues;
+mod fonts;
+mod history;
 mod layouts;
+#[cfg(not(target_arch = "wasm32"))]
+mod mdns;
+mod nmea;
+mod recording;
+mod simulator;
+mod theme;
 
 pub use app::DisplayApplication;
+pub use datatypes::DataValues;
+pub use recording::Recorder;
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use web_time::{Duration, Instant};
+
+/// Where mDNS queries are sent.
+pub const MDNS_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+
+/// The DNS-SD service types SignalK servers announce.
+const SERVICE_TYPES: [&str; 2] = ["_signalk-http._tcp.local", "_signalk-ws._tcp.local"];
+
+/// How long to collect answers for.
+const BROWSE_TIME: Duration = Duration::from_secs(3);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+/// Asks responders to answer straight to us instead of to the multicast group.
+const UNICAST_RESPONSE: u16 = 0x8000;
+
+/// A SignalK server found on the local network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredServer {
+    /// The instance name it announces, usually the vessel or host name.
+    pub name: String,
+    /// The address to use as the server address.
+    pub url: String,
+}
+
+/// Looks for SignalK servers on the local network in the background.
+#[derive(Default)]
+pub struct ServerBrowser {
+    servers: Vec<DiscoveredServer>,
+    error: Option<String>,
+    result_rx: Option<Receiver<std::io::Result<Vec<DiscoveredServer>>>>,
+}
+
+impl ServerBrowser {
+    /// Start a new search, sending the queries to `target`, normally `MDNS_ADDRESS`.
+    pub fn start(&mut self, target: SocketAddr) {
+        let (result_tx, result_rx) = channel();
+        self.result_rx = Some(result_rx);
+        std::thread::spawn(move || {
+            let result = browse(target, BROWSE_TIME);
+            if let Err(err) = result_tx.send(result) {
+                log::error!("Can't send found servers back {:?}", err);
+            }
+        });
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.result_rx.is_some()
+    }
+
+    /// Pick up the result of a search that has finished.
+    pub fn poll(&mut self) {
+        let Some(ref result_rx) = self.result_rx else {
+            return;
+        };
+        let result = match result_rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                Err(std::io::Error::other("Search stopped unexpectedly"))
+            }
+        };
+        self.result_rx = None;
+        match result {
+            Ok(servers) => {
+                log::info!("Found servers {:?}", servers);
+                self.servers = servers;
+                self.error = None;
+            }
+            Err(err) => {
+                log::warn!("Server search failed: {}", err);
+                self.error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn servers(&self) -> &[DiscoveredServer] {
+        &self.servers
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Ask for SignalK services and collect the answers for `duration`.
+///
+/// The queries come from an ephemeral port, so responders answer with legacy unicast
+/// replies and this doesn't compete with a system mDNS daemon for port 5353.
+pub fn browse(target: SocketAddr, duration: Duration) -> std::io::Result<Vec<DiscoveredServer>> {
+    let bind_address: SocketAddr = match target {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_address)?;
+    if target.ip().is_multicast() && target.is_ipv4() {
+        socket.set_multicast_ttl_v4(255)?;
+    }
+    socket.send_to(&query(), target)?;
+
+    let mut records = Records::default();
+    let deadline = Instant::now() + duration;
+    let mut buffer = [0u8; 9000];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(left))?;
+        match socket.recv_from(&mut buffer) {
+            Ok((length, from)) => {
+                if let Err(err) = records.add_message(&buffer[..length]) {
+                    log::debug!("Bad mDNS answer from {}: {}", from, err);
+                }
+            }
+            Err(err)
+                if err.kind() == std::io::ErrorKind::WouldBlock
+                    || err.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(records.servers())
+}
+
+/// A query for the PTR records of both SignalK service types.
+fn query() -> Vec<u8> {
+    let mut message = vec![0, 0, 0, 0];
+    message.extend_from_slice(&(SERVICE_TYPES.len() as u16).to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for service_type in SERVICE_TYPES {
+        for label in service_type.split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.push(0);
+        message.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        message.extend_from_slice(&(CLASS_IN | UNICAST_RESPONSE).to_be_bytes());
+    }
+    message
+}
+
+/// The records from all answers, by name.
+#[derive(Default)]
+struct Records {
+    /// Service type to instance names.
+    instances: Vec<(String, String)>,
+    /// Instance name to host and port.
+    services: HashMap<String, (String, u16)>,
+    addresses: HashMap<String, Vec<IpAddr>>,
+}
+
+impl Records {
+    fn add_message(&mut self, message: &[u8]) -> Result<(), &'static str> {
+        let count = |offset: usize| read_u16(message, offset).map(usize::from);
+        let questions = count(4)?;
+        let records = count(6)? + count(8)? + count(10)?;
+        let mut offset = 12;
+        for _ in 0..questions {
+            offset = read_name(message, offset)?.1 + 4;
+        }
+        for _ in 0..records {
+            let (name, after_name) = read_name(message, offset)?;
+            let record_type = read_u16(message, after_name)?;
+            let length = usize::from(read_u16(message, after_name + 8)?);
+            let data = after_name + 10;
+            let data_end = data + length;
+            if data_end > message.len() {
+                return Err("record past the end of the message");
+            }
+            match record_type {
+                TYPE_PTR => {
+                    let (instance, _) = read_name(message, data)?;
+                    self.instances.push((name.to_lowercase(), instance));
+                }
+                TYPE_SRV => {
+                    let port = read_u16(message, data + 4)?;
+                    let (host, _) = read_name(message, data + 6)?;
+                    self.services.insert(name.to_lowercase(), (host, port));
+                }
+                TYPE_A if length == 4 => {
+                    let octets: [u8; 4] = message[data..data_end].try_into().unwrap_or_default();
+                    self.add_address(name, Ipv4Addr::from(octets).into());
+                }
+                TYPE_AAAA if length == 16 => {
+                    let octets: [u8; 16] = message[data..data_end].try_into().unwrap_or_default();
+                    self.add_address(name, Ipv6Addr::from(octets).into());
+                }
+                _ => {}
+            }
+            offset = data_end;
+        }
+        Ok(())
+    }
+
+    fn add_address(&mut self, host: String, address: IpAddr) {
+        let addresses = self.addresses.entry(host.to_lowercase()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// A server for every announced instance we know the port of, by IPv4 address if
+    /// there is one as those work everywhere, else by host name.
+    fn servers(&self) -> Vec<DiscoveredServer> {
+        let mut servers: Vec<DiscoveredServer> = Vec::new();
+        for (service_type, instance) in &self.instances {
+            if !SERVICE_TYPES.contains(&service_type.as_str()) {
+                continue;
+            }
+            let Some((host, port)) = self.services.get(&instance.to_lowercase()) else {
+                continue;
+            };
+            let addresses = self.addresses.get(&host.to_lowercase());
+            let address = addresses
+                .and_then(|addresses| addresses.iter().find(|address| address.is_ipv4()))
+                .or(addresses.and_then(|addresses| addresses.first()));
+            let host = match address {
+                Some(IpAddr::V6(address)) => format!("[{}]", address),
+                Some(address) => address.to_string(),
+                None => host.trim_end_matches('.').to_string(),
+            };
+            let url = format!("http://{}:{}/signalk", host, port);
+            if servers.iter().any(|server| server.url == url) {
+                continue;
+            }
+            let name = instance
+                .strip_suffix(service_type.as_str())
+                .unwrap_or(instance)
+                .trim_end_matches('.')
+                .to_string();
+            servers.push(DiscoveredServer { name, url });
+        }
+        servers
+    }
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Result<u16, &'static str> {
+    message
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or("message too short")
+}
+
+/// Read a possibly compressed name, returning it and the offset after it.
+fn read_name(message: &[u8], offset: usize) -> Result<(String, usize), &'static str> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut end = None;
+    // Every pointer must go backwards, so this can't loop forever.
+    let mut limit = offset;
+    loop {
+        let length = *message
+            .get(position)
+            .ok_or("name past the end of the message")?;
+        match length {
+            0 => {
+                end.get_or_insert(position + 1);
+                break;
+            }
+            length if length & 0xc0 == 0xc0 => {
+                let pointer = usize::from(read_u16(message, position)? & 0x3fff);
+                if pointer >= limit {
+                    return Err("name pointer doesn't point backwards");
+                }
+                end.get_or_insert(position + 2);
+                limit = pointer;
+                position = pointer;
+            }
+            length => {
+                let label = message
+                    .get(position + 1..position + 1 + usize::from(length))
+                    .ok_or("label past the end of the message")?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + usize::from(length);
+            }
+        }
+    }
+    Ok((labels.join("."), end.unwrap_or(position)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an mDNS response, with names compressed by pointing at earlier ones.
+    struct Response {
+        bytes: Vec<u8>,
+        answers: u16,
+    }
+
+    impl Response {
+        fn new() -> Self {
+            let mut bytes = vec![0; 12];
+            bytes[2] = 0x84;
+            Self { bytes, answers: 0 }
+        }
+
+        /// Write the labels, ended by a pointer to `suffix` or the root. Returns where the
+        /// name starts.
+        fn name(&mut self, labels: &[&str], suffix: Option<usize>) -> usize {
+            let start = self.bytes.len();
+            for label in labels {
+                self.bytes.push(label.len() as u8);
+                self.bytes.extend_from_slice(label.as_bytes());
+            }
+            match suffix {
+                Some(pointer) => self
+                    .bytes
+                    .extend_from_slice(&(0xc000 | pointer as u16).to_be_bytes()),
+                None => self.bytes.push(0),
+            }
+            start
+        }
+
+        /// Write a record, returning where its name and its data start.
+        fn record(
+            &mut self,
+            labels: &[&str],
+            suffix: Option<usize>,
+            record_type: u16,
+            data: impl FnOnce(&mut Self),
+        ) -> (usize, usize) {
+            let name = self.name(labels, suffix);
+            self.bytes.extend_from_slice(&record_type.to_be_bytes());
+            self.bytes.extend_from_slice(&0x8001u16.to_be_bytes());
+            self.bytes.extend_from_slice(&120u32.to_be_bytes());
+            let length_at = self.bytes.len();
+            self.bytes.extend_from_slice(&[0, 0]);
+            data(self);
+            let length = (self.bytes.len() - length_at - 2) as u16;
+            self.bytes[length_at..length_at + 2].copy_from_slice(&length.to_be_bytes());
+            self.answers += 1;
+            (name, length_at + 2)
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            let answers = self.answers.to_be_bytes();
+            self.bytes[6..8].copy_from_slice(&answers);
+            self.bytes
+        }
+    }
+
+    const TYPE_TXT: u16 = 16;
+
+    /// An HTTP service on a host with an address, and a stream service on a host without.
+    fn answer() -> Vec<u8> {
+        let mut response = Response::new();
+        let (http_type, boat) = response.record(
+            &["_signalk-http", "_tcp", "local"],
+            None,
+            TYPE_PTR,
+            |response| {
+                response.name(&["boat"], Some(12));
+            },
+        );
+        let tcp = http_type + 1 + "_signalk-http".len();
+        let local = tcp + 1 + "_tcp".len();
+        let (_, srv) = response.record(&[], Some(boat), TYPE_SRV, |response| {
+            response.bytes.extend_from_slice(&[0, 0, 0, 0]);
+            response.bytes.extend_from_slice(&3000u16.to_be_bytes());
+            response.name(&["pi"], Some(local));
+        });
+        response.record(&[], Some(boat), TYPE_TXT, |response| {
+            response.bytes.push(9);
+            response.bytes.extend_from_slice(b"txtvers=1");
+        });
+        response.record(&[], Some(srv + 6), TYPE_A, |response| {
+            response.bytes.extend_from_slice(&[192, 168, 1, 20]);
+        });
+        let ws_type = response.bytes.len();
+        let (_, other_boat) = response.record(&["_signalk-ws"], Some(tcp), TYPE_PTR, |response| {
+            response.name(&["Other Boat"], Some(ws_type));
+        });
+        response.record(&[], Some(other_boat), TYPE_SRV, |response| {
+            response.bytes.extend_from_slice(&[0, 0, 0, 0]);
+            response.bytes.extend_from_slice(&3443u16.to_be_bytes());
+            response.name(&["other", "local"], None);
+        });
+        response.finish()
+    }
+
+    #[test]
+    fn browses_a_responder_on_loopback() {
+        let responder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = responder.local_addr().unwrap();
+        let answering = std::thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            let (length, from) = responder.recv_from(&mut buffer).unwrap();
+            assert_eq!(
+                read_u16(&buffer[..length], 4),
+                Ok(SERVICE_TYPES.len() as u16)
+            );
+            responder.send_to(&answer(), from).unwrap();
+        });
+        let servers = browse(target, Duration::from_millis(500)).unwrap();
+        answering.join().unwrap();
+        assert_eq!(
+            servers,
+            vec![
+                DiscoveredServer {
+                    name: "boat".to_string(),
+                    url: "http://192.168.1.20:3000/signalk".to_string(),
+                },
+                DiscoveredServer {
+                    name: "Other Boat".to_string(),
+                    url: "http://other.local:3443/signalk".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_answers() {
+        let answer = answer();
+        for length in [11, 20, answer.len() - 1] {
+            assert!(Records::default().add_message(&answer[..length]).is_err());
+        }
+    }
+
+    #[test]
+    fn reads_compressed_names() {
+        let mut message = vec![0; 12];
+        message.extend_from_slice(b"\x04boat\x05local\x00\x02pi\xc0\x11");
+        assert_eq!(read_name(&message, 12), Ok(("boat.local".to_string(), 24)));
+        assert_eq!(read_name(&message, 24), Ok(("pi.local".to_string(), 29)));
+    }
+
+    #[test]
+    fn rejects_name_pointer_loops() {
+        let mut message = vec![0; 12];
+        // A pointer to itself, then two pointers to each other.
+        message.extend_from_slice(&[0xc0, 12, 0xc0, 16, 0xc0, 14]);
+        assert!(read_name(&message, 12).is_err());
+        assert!(read_name(&message, 16).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_names() {
+        let mut message = vec![0; 12];
+        message.extend_from_slice(b"\x04boat\x05lo");
+        assert!(read_name(&message, 12).is_err());
+        // No end of the name.
+        assert!(read_name(&message[..17], 12).is_err());
+        // Half a pointer.
+        assert!(read_name(&[0xc0], 0).is_err());
+    }
+}
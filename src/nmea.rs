@@ -0,0 +1,572 @@
+use serde_json::{json, Value};
+use signalk::{V1DeltaFormat, V1UpdateType, V1UpdateValue};
+
+use crate::datasource::LOCAL_CONTEXT;
+
+const METERS_PER_SECOND_PER_KNOT: f64 = 1852.0 / 3600.0;
+const METERS_PER_FOOT: f64 = 0.3048;
+const METERS_PER_FATHOM: f64 = 1.8288;
+const KELVIN_AT_ZERO_CELSIUS: f64 = 273.15;
+
+/// A SignalK path, without the `self.` prefix, and its value in SI units.
+type PathValue = (&'static str, Value);
+
+/// Turn an NMEA 0183 sentence into a delta with the values it has. Sentences that aren't
+/// supported, or have no valid values, give `None`.
+pub(crate) fn sentence_to_delta(line: &str) -> Result<Option<V1DeltaFormat>, String> {
+    let values = parse_sentence(line)?;
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let talker = line.trim().get(1..3).unwrap_or_default();
+    let update = values
+        .into_iter()
+        .fold(
+            V1UpdateType::builder().ref_source(format!("nmea0183.{}", talker)),
+            |builder, (path, value)| {
+                builder.add_update(V1UpdateValue::new(path.to_string(), value))
+            },
+        )
+        .build();
+    Ok(Some(
+        V1DeltaFormat::builder()
+            .context(LOCAL_CONTEXT.to_string())
+            .add_update(update)
+            .build(),
+    ))
+}
+
+/// The values in a sentence. Unsupported sentences have none, a bad checksum is an error.
+pub(crate) fn parse_sentence(line: &str) -> Result<Vec<PathValue>, String> {
+    let line = line.trim();
+    let Some(body) = line.strip_prefix('$') else {
+        // Encapsulated sentences, like AIS, and noise.
+        return Ok(Vec::new());
+    };
+    let body = match body.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.trim(), 16)
+                .map_err(|_| format!("Bad checksum field in {:?}", line))?;
+            let actual = body.bytes().fold(0u8, |sum, byte| sum ^ byte);
+            if actual != expected {
+                return Err(format!("Checksum mismatch in {:?}", line));
+            }
+            body
+        }
+        None => body,
+    };
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    // Noise decoded from bytes that weren't text can be anything.
+    if address.starts_with('P') || address.len() != 5 || !address.is_ascii() {
+        return Ok(Vec::new());
+    }
+    let fields = Fields(&fields[1..]);
+    let values = match &address[2..] {
+        "RMC" => rmc(&fields),
+        "VHW" => vhw(&fields),
+        "MWV" => mwv(&fields),
+        "DBT" => dbt(&fields),
+        "DPT" => dpt(&fields),
+        "HDG" => hdg(&fields),
+        "HDT" => hdt(&fields),
+        "MTW" => mtw(&fields),
+        "XDR" => xdr(&fields),
+        "RSA" => rsa(&fields),
+        "XTE" => xte(&fields),
+        "APB" => apb(&fields),
+        _ => Vec::new(),
+    };
+    Ok(values)
+}
+
+/// The fields of a sentence after the address, where empty fields are missing values.
+struct Fields<'a>(&'a [&'a str]);
+
+impl Fields<'_> {
+    fn text(&self, index: usize) -> Option<&str> {
+        self.0.get(index).copied().filter(|field| !field.is_empty())
+    }
+
+    fn number(&self, index: usize) -> Option<f64> {
+        self.text(index).and_then(|field| field.parse().ok())
+    }
+
+    fn is(&self, index: usize, expected: &str) -> bool {
+        self.text(index) == Some(expected)
+    }
+
+    fn radians(&self, index: usize) -> Option<f64> {
+        self.number(index).map(f64::to_radians)
+    }
+}
+
+/// Degrees and minutes, `ddmm.mm` or `dddmm.mm`, with the hemisphere letter after it.
+fn coordinate(fields: &Fields<'_>, index: usize, negative: &str) -> Option<f64> {
+    let value = fields.number(index)?;
+    let degrees = (value / 100.0).trunc() + (value % 100.0) / 60.0;
+    Some(if fields.is(index + 1, negative) {
+        -degrees
+    } else {
+        degrees
+    })
+}
+
+/// Angles east are positive, west negative.
+fn east_positive(fields: &Fields<'_>, index: usize) -> Option<f64> {
+    let radians = fields.radians(index)?;
+    Some(if fields.is(index + 1, "W") {
+        -radians
+    } else {
+        radians
+    })
+}
+
+/// A distance to steer left is a vessel right of the track, positive in SignalK.
+fn cross_track_error(fields: &Fields<'_>, index: usize) -> Option<PathValue> {
+    let distance = fields.number(index)?;
+    let meters = match fields.text(index + 2) {
+        Some("K") => distance * 1000.0,
+        _ => distance * 1852.0,
+    };
+    let meters = if fields.is(index + 1, "R") {
+        -meters
+    } else {
+        meters
+    };
+    Some((
+        "navigation.course.calcValues.crossTrackError",
+        json!(meters),
+    ))
+}
+
+/// Recommended minimum navigation data: position, speed and course over ground, and time.
+fn rmc(fields: &Fields<'_>) -> Vec<PathValue> {
+    if !fields.is(1, "A") {
+        return Vec::new();
+    }
+    let mut values = Vec::new();
+    if let (Some(latitude), Some(longitude)) =
+        (coordinate(fields, 2, "S"), coordinate(fields, 4, "W"))
+    {
+        values.push((
+            "navigation.position",
+            json!({"latitude": latitude, "longitude": longitude}),
+        ));
+    }
+    if let Some(knots) = fields.number(6) {
+        values.push((
+            "navigation.speedOverGround",
+            json!(knots * METERS_PER_SECOND_PER_KNOT),
+        ));
+    }
+    if let Some(course) = fields.radians(7) {
+        values.push(("navigation.courseOverGroundTrue", json!(course)));
+    }
+    if let (Some(time), Some(date)) = (fields.text(0), fields.text(8)) {
+        if time.len() >= 6 && date.len() == 6 && time.is_ascii() && date.is_ascii() {
+            values.push((
+                "navigation.datetime",
+                json!(format!(
+                    "{}{}-{}-{}T{}:{}:{}Z",
+                    // Two digit years, from when GPS started in 1980.
+                    if &date[4..6] >= "80" { "19" } else { "20" },
+                    &date[4..6],
+                    &date[2..4],
+                    &date[0..2],
+                    &time[0..2],
+                    &time[2..4],
+                    &time[4..]
+                )),
+            ));
+        }
+    }
+    if let Some(variation) = east_positive(fields, 9) {
+        values.push(("navigation.magneticVariation", json!(variation)));
+    }
+    values
+}
+
+/// Water speed and heading.
+fn vhw(fields: &Fields<'_>) -> Vec<PathValue> {
+    let mut values = Vec::new();
+    if let Some(heading) = fields.radians(0) {
+        values.push(("navigation.headingTrue", json!(heading)));
+    }
+    if let Some(heading) = fields.radians(2) {
+        values.push(("navigation.headingMagnetic", json!(heading)));
+    }
+    let speed = fields
+        .number(4)
+        .map(|knots| knots * METERS_PER_SECOND_PER_KNOT)
+        .or(fields.number(6).map(|kmh| kmh / 3.6));
+    if let Some(speed) = speed {
+        values.push(("navigation.speedThroughWater", json!(speed)));
+    }
+    values
+}
+
+/// Wind speed and angle, apparent (relative) or true (theoretical).
+fn mwv(fields: &Fields<'_>) -> Vec<PathValue> {
+    if !fields.is(4, "A") {
+        return Vec::new();
+    }
+    let (angle_path, speed_path) = match fields.text(1) {
+        Some("R") => (
+            "environment.wind.angleApparent",
+            "environment.wind.speedApparent",
+        ),
+        Some("T") => (
+            "environment.wind.angleTrueWater",
+            "environment.wind.speedTrue",
+        ),
+        _ => return Vec::new(),
+    };
+    let mut values = Vec::new();
+    if let Some(angle) = fields.number(0) {
+        // NMEA gives 0 to 360 degrees, SignalK negative angles to port.
+        let angle = if angle > 180.0 { angle - 360.0 } else { angle };
+        values.push((angle_path, json!(angle.to_radians())));
+    }
+    if let Some(speed) = fields.number(2) {
+        let speed = match fields.text(3) {
+            Some("K") => Some(speed / 3.6),
+            Some("M") => Some(speed),
+            Some("N") => Some(speed * METERS_PER_SECOND_PER_KNOT),
+            _ => None,
+        };
+        if let Some(speed) = speed {
+            values.push((speed_path, json!(speed)));
+        }
+    }
+    values
+}
+
+/// Depth below transducer, in feet, meters or fathoms.
+fn dbt(fields: &Fields<'_>) -> Vec<PathValue> {
+    let depth = fields
+        .number(2)
+        .or(fields.number(0).map(|feet| feet * METERS_PER_FOOT))
+        .or(fields.number(4).map(|fathoms| fathoms * METERS_PER_FATHOM));
+    depth
+        .map(|depth| vec![("environment.depth.belowTransducer", json!(depth))])
+        .unwrap_or_default()
+}
+
+/// Depth below transducer, with the offset to the waterline, positive, or keel, negative.
+fn dpt(fields: &Fields<'_>) -> Vec<PathValue> {
+    let Some(depth) = fields.number(0) else {
+        return Vec::new();
+    };
+    let mut values = vec![("environment.depth.belowTransducer", json!(depth))];
+    match fields.number(1) {
+        Some(offset) if offset > 0.0 => {
+            values.push(("environment.depth.belowSurface", json!(depth + offset)))
+        }
+        Some(offset) if offset < 0.0 => {
+            values.push(("environment.depth.belowKeel", json!(depth + offset)))
+        }
+        _ => {}
+    }
+    values
+}
+
+/// Magnetic heading, with deviation and variation to get the true heading.
+fn hdg(fields: &Fields<'_>) -> Vec<PathValue> {
+    let Some(sensor_heading) = fields.radians(0) else {
+        return Vec::new();
+    };
+    let magnetic = sensor_heading + east_positive(fields, 1).unwrap_or(0.0);
+    let mut values = vec![(
+        "navigation.headingMagnetic",
+        json!(magnetic.rem_euclid(std::f64::consts::TAU)),
+    )];
+    if let Some(variation) = east_positive(fields, 3) {
+        values.push(("navigation.magneticVariation", json!(variation)));
+        values.push((
+            "navigation.headingTrue",
+            json!((magnetic + variation).rem_euclid(std::f64::consts::TAU)),
+        ));
+    }
+    values
+}
+
+/// True heading.
+fn hdt(fields: &Fields<'_>) -> Vec<PathValue> {
+    fields
+        .radians(0)
+        .map(|heading| vec![("navigation.headingTrue", json!(heading))])
+        .unwrap_or_default()
+}
+
+/// Water temperature in degrees Celsius.
+fn mtw(fields: &Fields<'_>) -> Vec<PathValue> {
+    match (fields.number(0), fields.text(1)) {
+        (Some(celsius), Some("C")) => vec![(
+            "environment.water.temperature",
+            json!(celsius + KELVIN_AT_ZERO_CELSIUS),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// Transducer measurements, in groups of type, value, unit and name. Only the air and
+/// water temperature, barometric pressure and battery voltage are used, recognised by the
+/// names common instruments give them. Other temperatures and pressures, like those of an
+/// engine, are skipped.
+fn xdr(fields: &Fields<'_>) -> Vec<PathValue> {
+    let mut values = Vec::new();
+    for index in (0..fields.0.len()).step_by(4) {
+        let (Some(kind), Some(value)) = (fields.text(index), fields.number(index + 1)) else {
+            continue;
+        };
+        let unit = fields.text(index + 2);
+        let name = fields.text(index + 3).unwrap_or_default().to_uppercase();
+        match (kind, unit) {
+            ("C", Some("C")) if name.contains("WATER") || name.contains("WTHI") => values.push((
+                "environment.water.temperature",
+                json!(value + KELVIN_AT_ZERO_CELSIUS),
+            )),
+            ("C", Some("C")) if name.contains("AIR") => values.push((
+                "environment.outside.temperature",
+                json!(value + KELVIN_AT_ZERO_CELSIUS),
+            )),
+            ("P", Some("B")) if name.contains("BARO") => {
+                values.push(("environment.outside.pressure", json!(value * 100_000.0)))
+            }
+            ("P", Some("P")) if name.contains("BARO") => {
+                values.push(("environment.outside.pressure", json!(value)))
+            }
+            ("U", Some("V")) if name.contains("BAT") => {
+                values.push(("electrical.batteries.house.voltage", json!(value)))
+            }
+            _ => {}
+        }
+    }
+    values
+}
+
+/// Rudder angle, from the starboard, or only, rudder sensor.
+fn rsa(fields: &Fields<'_>) -> Vec<PathValue> {
+    match (fields.radians(0), fields.is(1, "A")) {
+        (Some(angle), true) => vec![("steering.rudderAngle", json!(angle))],
+        _ => Vec::new(),
+    }
+}
+
+/// Cross track error.
+fn xte(fields: &Fields<'_>) -> Vec<PathValue> {
+    if !fields.is(0, "A") || !fields.is(1, "A") {
+        return Vec::new();
+    }
+    cross_track_error(fields, 2).into_iter().collect()
+}
+
+/// Autopilot sentence B: cross track error and the bearing to the destination.
+fn apb(fields: &Fields<'_>) -> Vec<PathValue> {
+    if !fields.is(0, "A") || !fields.is(1, "A") {
+        return Vec::new();
+    }
+    let mut values: Vec<PathValue> = cross_track_error(fields, 2).into_iter().collect();
+    if let Some(bearing) = fields.radians(10) {
+        let path = if fields.is(11, "M") {
+            "navigation.course.calcValues.bearingMagnetic"
+        } else {
+            "navigation.course.calcValues.bearingTrue"
+        };
+        values.push((path, json!(bearing)));
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sentence with its checksum added.
+    fn sentence(body: &str) -> String {
+        let checksum = body.bytes().fold(0u8, |sum, byte| sum ^ byte);
+        format!("${}*{:02X}", body, checksum)
+    }
+
+    fn number(values: &[PathValue], path: &str) -> f64 {
+        values
+            .iter()
+            .find(|(value_path, _)| *value_path == path)
+            .and_then(|(_, value)| value.as_f64())
+            .unwrap_or_else(|| panic!("No number for {} in {:?}", path, values))
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn checks_checksums() {
+        let good = sentence("IIHDT,90.0,T");
+        assert_near(
+            number(&parse_sentence(&good).unwrap(), "navigation.headingTrue"),
+            90f64.to_radians(),
+        );
+        assert!(parse_sentence("$IIHDT,90.0,T*00").is_err());
+        assert!(parse_sentence("$IIHDT,90.0,T*ZZ").is_err());
+        // A sentence without a checksum is taken as it is.
+        assert_eq!(parse_sentence("$IIHDT,90.0,T").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_rmc() {
+        let values = parse_sentence(&sentence(
+            "GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W",
+        ))
+        .unwrap();
+        let position = &values
+            .iter()
+            .find(|(path, _)| *path == "navigation.position")
+            .unwrap()
+            .1;
+        assert_near(position["latitude"].as_f64().unwrap(), 48.0 + 7.038 / 60.0);
+        assert_near(position["longitude"].as_f64().unwrap(), 11.0 + 31.0 / 60.0);
+        assert_near(
+            number(&values, "navigation.speedOverGround"),
+            22.4 * METERS_PER_SECOND_PER_KNOT,
+        );
+        assert_near(
+            number(&values, "navigation.courseOverGroundTrue"),
+            84.4f64.to_radians(),
+        );
+        assert_near(
+            number(&values, "navigation.magneticVariation"),
+            -3.1f64.to_radians(),
+        );
+        assert!(values.contains(&("navigation.datetime", json!("1994-03-23T12:35:19Z"))));
+        // Without a valid fix there is nothing to use.
+        let void = sentence("GPRMC,123519,V,4807.038,N,01131.000,E,022.4,084.4,230394,,");
+        assert!(parse_sentence(&void).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_mwv() {
+        let apparent = parse_sentence(&sentence("WIMWV,270.0,R,10.0,N,A")).unwrap();
+        assert_near(
+            number(&apparent, "environment.wind.angleApparent"),
+            -90f64.to_radians(),
+        );
+        assert_near(
+            number(&apparent, "environment.wind.speedApparent"),
+            10.0 * METERS_PER_SECOND_PER_KNOT,
+        );
+        let true_wind = parse_sentence(&sentence("WIMWV,45.0,T,36.0,K,A")).unwrap();
+        assert_near(
+            number(&true_wind, "environment.wind.angleTrueWater"),
+            45f64.to_radians(),
+        );
+        assert!(!true_wind
+            .iter()
+            .any(|(path, _)| *path == "environment.wind.angleTrueGround"));
+        assert_near(number(&true_wind, "environment.wind.speedTrue"), 10.0);
+        let invalid = parse_sentence(&sentence("WIMWV,45.0,T,36.0,K,V")).unwrap();
+        assert!(invalid.is_empty());
+    }
+
+    #[test]
+    fn parses_dpt() {
+        let surface = parse_sentence(&sentence("SDDPT,5.2,0.5")).unwrap();
+        assert_near(number(&surface, "environment.depth.belowTransducer"), 5.2);
+        assert_near(number(&surface, "environment.depth.belowSurface"), 5.7);
+        let keel = parse_sentence(&sentence("SDDPT,5.2,-0.3")).unwrap();
+        assert_near(number(&keel, "environment.depth.belowKeel"), 4.9);
+    }
+
+    #[test]
+    fn parses_hdg() {
+        let values = parse_sentence(&sentence("HCHDG,100.0,2.0,W,5.0,E")).unwrap();
+        assert_near(
+            number(&values, "navigation.headingMagnetic"),
+            98f64.to_radians(),
+        );
+        assert_near(
+            number(&values, "navigation.magneticVariation"),
+            5f64.to_radians(),
+        );
+        assert_near(
+            number(&values, "navigation.headingTrue"),
+            103f64.to_radians(),
+        );
+    }
+
+    #[test]
+    fn parses_xdr() {
+        let values = parse_sentence(&sentence(
+            "IIXDR,C,19.5,C,AIRTEMP,P,1.013,B,BARO,U,12.6,V,BATTERY",
+        ))
+        .unwrap();
+        assert_near(
+            number(&values, "environment.outside.temperature"),
+            19.5 + KELVIN_AT_ZERO_CELSIUS,
+        );
+        assert_near(number(&values, "environment.outside.pressure"), 101_300.0);
+        assert_near(number(&values, "electrical.batteries.house.voltage"), 12.6);
+    }
+
+    #[test]
+    fn skips_other_xdr_transducers() {
+        let values = parse_sentence(&sentence(
+            "IIXDR,C,85.0,C,ENGINETEMP,C,410.0,C,EXHAUST_TEMP,P,3.5,B,OILPRESS,P,2000000,P,HYDRAULIC,P,1.013,B,",
+        ))
+        .unwrap();
+        assert!(values.is_empty());
+        let values = parse_sentence(&sentence(
+            "IIXDR,C,12.5,C,ENV_OUTAIR_T,P,101300,P,Barometer,C,85.0,C,ENGINE#0",
+        ))
+        .unwrap();
+        assert_near(
+            number(&values, "environment.outside.temperature"),
+            12.5 + KELVIN_AT_ZERO_CELSIUS,
+        );
+        assert_near(number(&values, "environment.outside.pressure"), 101_300.0);
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn parses_apb() {
+        let values =
+            parse_sentence(&sentence("GPAPB,A,A,0.10,R,N,V,V,011,T,DEST,012,T,012,T")).unwrap();
+        assert_near(
+            number(&values, "navigation.course.calcValues.crossTrackError"),
+            -0.1 * 1852.0,
+        );
+        assert_near(
+            number(&values, "navigation.course.calcValues.bearingTrue"),
+            12f64.to_radians(),
+        );
+        let magnetic =
+            parse_sentence(&sentence("GPAPB,A,A,0.10,L,N,V,V,011,M,DEST,012,M,012,M")).unwrap();
+        assert_near(
+            number(&magnetic, "navigation.course.calcValues.bearingMagnetic"),
+            12f64.to_radians(),
+        );
+    }
+
+    #[test]
+    fn skips_garbage() {
+        for line in [
+            "$G\u{FFFD}A,1,2",
+            "$\u{FFFD}A,1,2",
+            "$\u{FFFD}\u{FFFD}",
+            "$\u{FFFD}",
+            "\u{FFFD}\u{FFFD}$GPRMC",
+            "!AIVDM,1,1,,A,13aG?P0P00PD;88MD5MTDww@2<0L,0*5C",
+            "$PGRME,15.0,M,45.0,M,25.0,M",
+            "",
+        ] {
+            assert_eq!(parse_sentence(line), Ok(Vec::new()), "{:?}", line);
+            assert!(sentence_to_delta(line).unwrap().is_none(), "{:?}", line);
+        }
+    }
+}
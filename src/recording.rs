@@ -0,0 +1,384 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+
+#[cfg(not(target_arch = "wasm32"))]
+use serde::Deserialize;
+use signalk::V1DeltaFormat;
+#[cfg(not(target_arch = "wasm32"))]
+use web_time::{Duration, Instant};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::datasource::{DeltaSource, LOCAL_CONTEXT};
+
+/// A line of a recording, a delta and when it was received.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct RecordedDelta<S, D> {
+    /// Milliseconds since the Unix epoch.
+    time: u64,
+    /// The server's own vessel, deltas about it are replayed as our own vessel.
+    #[serde(rename = "self")]
+    self_context: S,
+    delta: D,
+}
+
+/// Writes the deltas received to a JSON Lines file, a delta per line.
+pub struct Recorder {
+    path: String,
+    writer: LineWriter<File>,
+    count: u64,
+    /// Why writing stopped, nothing more is written after an error.
+    error: Option<String>,
+}
+
+impl Recorder {
+    /// Start a recording, replacing the file if there is one.
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("Can't create {}: {}", path, err))?;
+        log::info!("Recording deltas to {}", path);
+        Ok(Self {
+            path: path.to_string(),
+            writer: LineWriter::new(file),
+            count: 0,
+            error: None,
+        })
+    }
+
+    pub fn record(&mut self, self_context: &str, delta: &V1DeltaFormat) {
+        if self.error.is_some() {
+            return;
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let line = RecordedDelta {
+            time,
+            self_context,
+            delta,
+        };
+        let written = serde_json::to_writer(&mut self.writer, &line)
+            .map_err(|err| err.to_string())
+            .and_then(|()| self.writer.write_all(b"\n").map_err(|err| err.to_string()));
+        match written {
+            Ok(()) => self.count += 1,
+            Err(err) => {
+                log::error!("Recording to {} failed: {}", self.path, err);
+                self.error = Some(format!("Writing {} failed: {}", self.path, err));
+            }
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The number of deltas written.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// How a replay moves through the recording.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pace {
+    /// The recording plays this many times faster than it was recorded.
+    Speed(f64),
+    /// A delta each time `step` is called.
+    Stepped,
+}
+
+/// The time, in the recording, between its last delta and the first one of the next pass.
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_PAUSE: Duration = Duration::from_secs(1);
+
+/// A recording read into memory.
+#[cfg(not(target_arch = "wasm32"))]
+struct Recording {
+    self_context: String,
+    /// The deltas and how long after the first one they were received.
+    deltas: Vec<(Duration, serde_json::Value)>,
+}
+
+/// Plays a file written by `Recorder` back, over and over, so the display runs without a server.
+/// Browsers can't read files, so there is no replay on the web.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ReplaySource {
+    /// The recording, or why it can't be replayed.
+    recording: Result<Recording, String>,
+    pace: Pace,
+    /// The index of the next delta to send.
+    next: usize,
+    /// When the current pass through the recording started.
+    started: Instant,
+    /// Steps asked for that haven't been sent yet.
+    steps: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ReplaySource {
+    /// Replay the recording at a path like `/home/me/sail.jsonl`. Add `?speed=4` to play it
+    /// faster, or `?step` to go a delta at a time.
+    pub(crate) fn open(address: &str) -> Self {
+        let (path, query) = address.split_once('?').unwrap_or((address, ""));
+        let (pace, recording) = match parse_pace(query) {
+            Ok(pace) => (pace, read_recording(path)),
+            Err(reason) => (Pace::Speed(1.0), Err(reason)),
+        };
+        if let Ok(ref recording) = recording {
+            log::info!(
+                "Replaying {} deltas from {} {:?}",
+                recording.deltas.len(),
+                path,
+                pace
+            );
+        }
+        Self {
+            recording,
+            pace,
+            next: 0,
+            started: Instant::now(),
+            // Show the first delta straight away, so there is something on the display.
+            steps: 1,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DeltaSource for ReplaySource {
+    fn recv_deltas(&mut self) -> Result<Vec<V1DeltaFormat>, String> {
+        let recording = self.recording.as_ref().map_err(String::clone)?;
+        if self.next >= recording.deltas.len() {
+            let pass_over = match self.pace {
+                Pace::Speed(speed) => {
+                    let (last_offset, _) = recording.deltas[recording.deltas.len() - 1];
+                    self.started.elapsed().as_secs_f64() * speed
+                        >= (last_offset + REPLAY_PAUSE).as_secs_f64()
+                }
+                Pace::Stepped => true,
+            };
+            if !pass_over {
+                return Ok(Vec::new());
+            }
+            self.next = 0;
+            self.started = Instant::now();
+        }
+        let mut deltas = Vec::new();
+        while let Some((offset, delta)) = recording.deltas.get(self.next) {
+            let due = match self.pace {
+                Pace::Speed(speed) => {
+                    offset.as_secs_f64() <= self.started.elapsed().as_secs_f64() * speed
+                }
+                Pace::Stepped => self.steps > 0,
+            };
+            if !due {
+                break;
+            }
+            self.steps = self.steps.saturating_sub(1);
+            deltas.push(
+                V1DeltaFormat::deserialize(delta)
+                    .map_err(|err| format!("Can't replay delta: {}", err))?,
+            );
+            self.next += 1;
+        }
+        Ok(deltas)
+    }
+
+    fn self_context(&self) -> &str {
+        self.recording
+            .as_ref()
+            .map_or(LOCAL_CONTEXT, |recording| recording.self_context.as_str())
+    }
+
+    fn is_stepped(&self) -> bool {
+        self.pace == Pace::Stepped
+    }
+
+    fn step(&mut self) {
+        self.steps += 1;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_pace(query: &str) -> Result<Pace, String> {
+    match query.split_once('=') {
+        None if query.is_empty() => Ok(Pace::Speed(1.0)),
+        None if query == "step" => Ok(Pace::Stepped),
+        Some(("speed", speed)) => match speed.parse::<f64>() {
+            Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(Pace::Speed(speed)),
+            _ => Err(format!("{} isn't a replay speed", speed)),
+        },
+        _ => Err(format!(
+            "Unknown replay option {}, use speed=<factor> or step",
+            query
+        )),
+    }
+}
+
+/// Read a recording, skipping lines that aren't recorded deltas.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_recording(path: &str) -> Result<Recording, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+    let mut first: Option<(u64, String)> = None;
+    let mut deltas = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded = serde_json::from_str::<RecordedDelta<String, serde_json::Value>>(line)
+            .and_then(|recorded| V1DeltaFormat::deserialize(&recorded.delta).map(|_| recorded));
+        let recorded = match recorded {
+            Ok(recorded) => recorded,
+            Err(err) => {
+                log::warn!("Skipping line {} of {}: {}", index + 1, path, err);
+                continue;
+            }
+        };
+        let (start, _) = first.get_or_insert((recorded.time, recorded.self_context));
+        let offset = Duration::from_millis(recorded.time.saturating_sub(*start));
+        deltas.push((offset, recorded.delta));
+    }
+    match first {
+        Some((_, self_context)) => Ok(Recording {
+            self_context,
+            deltas,
+        }),
+        None => Err(format!("{} has no recorded deltas", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELF: &str = "vessels.urn:mrn:signalk:uuid:c0d79334-4e25-4245-8892-54e8ccc8021d";
+
+    /// A recording in the temporary directory, removed when dropped.
+    struct TempRecording(std::path::PathBuf);
+
+    impl TempRecording {
+        fn new(name: &str, text: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "signalk-recording-{}-{}.jsonl",
+                std::process::id(),
+                name
+            ));
+            std::fs::write(&path, text).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempRecording {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn line(time: u64, speed: f64) -> String {
+        format!(
+            r#"{{"time":{},"self":"{}","delta":{{"context":"{}","updates":[{{"values":[{{"path":"navigation.speedThroughWater","value":{}}}]}}]}}}}"#,
+            time, SELF, SELF, speed
+        )
+    }
+
+    #[test]
+    fn parses_paces() {
+        assert_eq!(parse_pace(""), Ok(Pace::Speed(1.0)));
+        assert_eq!(parse_pace("speed=4"), Ok(Pace::Speed(4.0)));
+        assert_eq!(parse_pace("speed=0.5"), Ok(Pace::Speed(0.5)));
+        assert_eq!(parse_pace("step"), Ok(Pace::Stepped));
+        for query in [
+            "speed=0",
+            "speed=-1",
+            "speed=inf",
+            "speed=fast",
+            "fast",
+            "step=1",
+        ] {
+            assert!(parse_pace(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn reads_recordings() {
+        let text = [
+            line(1_000, 1.0),
+            "not json".to_string(),
+            String::new(),
+            r#"{"time":1200,"self":"x","delta":{"updates":"wrong"}}"#.to_string(),
+            line(1_500, 2.0),
+            line(3_000, 3.0),
+        ]
+        .join("\n");
+        let file = TempRecording::new("read", &text);
+        let recording = read_recording(file.path()).unwrap();
+        assert_eq!(recording.self_context, SELF);
+        let offsets: Vec<_> = recording
+            .deltas
+            .iter()
+            .map(|(offset, _)| offset.as_millis())
+            .collect();
+        assert_eq!(offsets, vec![0, 500, 2000]);
+    }
+
+    #[test]
+    fn rejects_recordings_without_deltas() {
+        let file = TempRecording::new("empty", "not json\n{}\n");
+        let error = read_recording(file.path()).err().unwrap();
+        assert!(error.ends_with("has no recorded deltas"), "{}", error);
+        assert!(read_recording("/nonexistent/recording.jsonl").is_err());
+    }
+
+    /// The speeds in replayed deltas.
+    fn speeds(deltas: Vec<V1DeltaFormat>) -> Vec<f64> {
+        deltas
+            .iter()
+            .flat_map(|delta| delta.updates.iter())
+            .flat_map(|update| update.values.iter().flatten())
+            .filter_map(|value| value.value.as_f64())
+            .collect()
+    }
+
+    #[test]
+    fn steps_through_recordings() {
+        let text = [line(1_000, 1.0), line(2_000, 2.0)].join("\n");
+        let file = TempRecording::new("step", &text);
+        let mut replay = ReplaySource::open(&format!("{}?step", file.path()));
+        assert!(replay.is_stepped());
+        assert_eq!(replay.self_context(), SELF);
+        // The first delta is shown straight away, then one for each step, over and over.
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), vec![1.0]);
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), Vec::<f64>::new());
+        for expected in [2.0, 1.0, 2.0] {
+            replay.step();
+            assert_eq!(speeds(replay.recv_deltas().unwrap()), vec![expected]);
+        }
+    }
+
+    #[test]
+    fn pauses_between_passes() {
+        // Deltas received at the same time are all due at once, then not again until the
+        // pause after the last one, a quarter of a second at four times the speed.
+        let text = [line(1_000, 1.0), line(1_000, 2.0)].join("\n");
+        let file = TempRecording::new("speed", &text);
+        let mut replay = ReplaySource::open(&format!("{}?speed=4", file.path()));
+        assert!(!replay.is_stepped());
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), vec![1.0, 2.0]);
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), Vec::<f64>::new());
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), Vec::<f64>::new());
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), vec![1.0, 2.0]);
+        assert_eq!(speeds(replay.recv_deltas().unwrap()), Vec::<f64>::new());
+    }
+}
@@ -0,0 +1,508 @@
+use std::f64::consts::{PI, TAU};
+
+use serde_json::{json, Value};
+use signalk::{V1DeltaFormat, V1UpdateType, V1UpdateValue};
+use web_time::{Duration, Instant};
+
+use crate::datasource::{DeltaSource, LOCAL_CONTEXT};
+
+/// The simulated time between deltas.
+const TICK: Duration = Duration::from_secs(1);
+/// The most ticks simulated in one call, so a display that was paused doesn't stall catching up.
+const MAX_TICKS_PER_CALL: u32 = 10;
+
+/// When the simulation starts, in seconds since the Unix epoch, 2024-06-15T10:00:00Z. A
+/// fixed time, so `navigation.datetime` is the same for a seed too.
+const START_TIME: f64 = 1_718_445_600.0;
+
+const EARTH_RADIUS: f64 = 6_371_000.0;
+const KELVIN_AT_ZERO_CELSIUS: f64 = 273.15;
+
+/// Where the boat starts, in degrees, off Gothenburg.
+const START_LATITUDE: f64 = 57.66;
+const START_LONGITUDE: f64 = 11.78;
+const MAGNETIC_VARIATION_DEGREES: f64 = 4.5;
+
+/// The direction the wind shifts around and the speed it gusts around, in m/s.
+const MEAN_WIND_DIRECTION_DEGREES: f64 = 225.0;
+const MEAN_WIND_SPEED: f64 = 6.0;
+/// The current, in the direction it flows.
+const CURRENT_SET_DEGREES: f64 = 70.0;
+const CURRENT_DRIFT: f64 = 0.3;
+
+/// The course, a beat to the windward mark followed by two reaches back to the start.
+/// Each leg is a bearing from the mean wind direction and a length in meters.
+const COURSE_LEGS: [(f64, f64); 2] = [(0.0, 2000.0), (120.0, 1800.0)];
+/// How close to a mark counts as rounding it.
+const MARK_RADIUS: f64 = 40.0;
+
+const CLOSE_HAULED_DEGREES: f64 = 42.0;
+/// How far the mark may be on the other tack's side of the wind before tacking.
+const TACK_ANGLE_DEGREES: f64 = 15.0;
+const TURN_RATE_DEGREES: f64 = 5.0;
+/// How quickly the boat speed follows the polar, in seconds.
+const ACCELERATION_TIME: f64 = 8.0;
+const HULL_SPEED: f64 = 3.9;
+
+/// How far the transducer is below the surface, and the keel below the transducer.
+const TRANSDUCER_DEPTH: f64 = 0.5;
+const KEEL_BELOW_TRANSDUCER: f64 = 1.4;
+
+const BATTERY_CAPACITY_AH: f64 = 200.0;
+const BATTERY_BASE_LOAD: f64 = 4.0;
+
+/// SplitMix64, small and good enough for simulated noise, and the same on every platform.
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Roughly normal with a standard deviation of one.
+    fn normal(&mut self) -> f64 {
+        (0..12).map(|_| self.unit()).sum::<f64>() - 6.0
+    }
+}
+
+/// A value that wanders around `mean` and is pulled back to it over `time` seconds.
+struct Wander {
+    value: f64,
+    mean: f64,
+    deviation: f64,
+    time: f64,
+}
+
+impl Wander {
+    fn new(mean: f64, deviation: f64, time: f64) -> Self {
+        Self {
+            value: mean,
+            mean,
+            deviation,
+            time,
+        }
+    }
+
+    fn step(&mut self, random: &mut Random, dt: f64) -> f64 {
+        let pull = (self.mean - self.value) * dt / self.time;
+        let noise = self.deviation * (2.0 * dt / self.time).sqrt() * random.normal();
+        self.value += pull + noise;
+        self.value
+    }
+}
+
+/// A boat sailing a triangle over and over, with the wind shifting and gusting, and the
+/// instruments it would have. The same seed gives the same values, tick by tick.
+pub(crate) struct Simulator {
+    random: Random,
+    /// Simulated seconds since the start.
+    time: f64,
+    wind_shift: Wander,
+    wind_speed: Wander,
+    air_temperature: Wander,
+    pressure: Wander,
+    /// Meters north and east of the start.
+    north: f64,
+    east: f64,
+    heading: f64,
+    speed: f64,
+    rudder: f64,
+    /// 1 on starboard tack, with the wind from starboard, -1 on port.
+    tack: f64,
+    marks: Vec<(f64, f64)>,
+    next_mark: usize,
+    log: f64,
+    trip_log: f64,
+    battery_used_ah: f64,
+}
+
+impl Simulator {
+    pub(crate) fn new(seed: u64) -> Self {
+        let mean_wind = MEAN_WIND_DIRECTION_DEGREES.to_radians();
+        let mut marks = Vec::new();
+        let (mut north, mut east) = (0.0, 0.0);
+        for (bearing, length) in COURSE_LEGS {
+            let bearing = mean_wind + f64::to_radians(bearing);
+            north += length * bearing.cos();
+            east += length * bearing.sin();
+            marks.push((north, east));
+        }
+        marks.push((0.0, 0.0));
+        Self {
+            random: Random(seed),
+            time: 0.0,
+            wind_shift: Wander::new(0.0, 8f64.to_radians(), 300.0),
+            wind_speed: Wander::new(MEAN_WIND_SPEED, 1.2, 30.0),
+            air_temperature: Wander::new(18.0, 0.5, 600.0),
+            pressure: Wander::new(101_300.0, 150.0, 3600.0),
+            north: 0.0,
+            east: 0.0,
+            heading: mean_wind - CLOSE_HAULED_DEGREES.to_radians(),
+            speed: 2.0,
+            rudder: 0.0,
+            tack: 1.0,
+            marks,
+            next_mark: 0,
+            log: 4_250_000.0,
+            trip_log: 0.0,
+            battery_used_ah: 0.0,
+        }
+    }
+
+    /// Move the boat on by `TICK` and give a delta with all the instrument values.
+    pub(crate) fn tick(&mut self) -> V1DeltaFormat {
+        let dt = TICK.as_secs_f64();
+        self.time += dt;
+
+        // The wind over the ground, and over the water, which is what the boat sails in.
+        let wind_direction =
+            MEAN_WIND_DIRECTION_DEGREES.to_radians() + self.wind_shift.step(&mut self.random, dt);
+        let wind_speed = self.wind_speed.step(&mut self.random, dt).max(0.5);
+        let current_set = CURRENT_SET_DEGREES.to_radians();
+        let (current_north, current_east) = (
+            CURRENT_DRIFT * current_set.cos(),
+            CURRENT_DRIFT * current_set.sin(),
+        );
+        // Wind velocities point where the wind blows to, the opposite of its direction.
+        let (ground_wind_north, ground_wind_east) = (
+            -wind_speed * wind_direction.cos(),
+            -wind_speed * wind_direction.sin(),
+        );
+        let (water_wind_north, water_wind_east) = (
+            ground_wind_north - current_north,
+            ground_wind_east - current_east,
+        );
+        let water_wind_speed = water_wind_north.hypot(water_wind_east);
+        let water_wind_direction = (-water_wind_east).atan2(-water_wind_north);
+
+        // Steer for the next mark, beating in tacks when it is upwind.
+        let (mark_north, mark_east) = self.marks[self.next_mark];
+        let mut distance = (mark_north - self.north).hypot(mark_east - self.east);
+        if distance < MARK_RADIUS {
+            self.next_mark = (self.next_mark + 1) % self.marks.len();
+            let (mark_north, mark_east) = self.marks[self.next_mark];
+            distance = (mark_north - self.north).hypot(mark_east - self.east);
+        }
+        let (mark_north, mark_east) = self.marks[self.next_mark];
+        let bearing = (mark_east - self.east).atan2(mark_north - self.north);
+        let mark_wind_angle = normalize(water_wind_direction - bearing);
+        let close_hauled = CLOSE_HAULED_DEGREES.to_radians();
+        let target_heading = if mark_wind_angle.abs() >= close_hauled {
+            self.tack = mark_wind_angle.signum();
+            bearing
+        } else {
+            if mark_wind_angle.signum() != self.tack
+                && mark_wind_angle.abs() > TACK_ANGLE_DEGREES.to_radians()
+            {
+                self.tack = -self.tack;
+            }
+            water_wind_direction - self.tack * close_hauled
+        };
+        let turn = normalize(target_heading - self.heading);
+        let max_turn = TURN_RATE_DEGREES.to_radians() * dt;
+        self.heading = (self.heading + turn.clamp(-max_turn, max_turn)).rem_euclid(TAU);
+        self.rudder = (turn * 0.5).clamp(-0.6, 0.6) + 0.01 * self.random.normal();
+
+        // The boat speed follows the polar, slowly enough that tacks cost speed.
+        let true_wind_angle = normalize(water_wind_direction - self.heading);
+        let target_speed = polar_speed(water_wind_speed, true_wind_angle.abs());
+        self.speed += (target_speed - self.speed) * dt / ACCELERATION_TIME;
+        let (ground_north, ground_east) = (
+            self.speed * self.heading.cos() + current_north,
+            self.speed * self.heading.sin() + current_east,
+        );
+        self.north += ground_north * dt;
+        self.east += ground_east * dt;
+        self.log += self.speed * dt;
+        self.trip_log += self.speed * dt;
+        let speed_over_ground = ground_north.hypot(ground_east);
+        let course_over_ground = ground_east.atan2(ground_north).rem_euclid(TAU);
+
+        // The wind felt on board.
+        let (apparent_north, apparent_east) = (
+            ground_wind_north - ground_north,
+            ground_wind_east - ground_east,
+        );
+        let apparent_speed = apparent_north.hypot(apparent_east);
+        let apparent_angle = normalize((-apparent_east).atan2(-apparent_north) - self.heading);
+
+        // Progress along the leg from the previous mark.
+        let (from_north, from_east) =
+            self.marks[(self.next_mark + self.marks.len() - 1) % self.marks.len()];
+        let (leg_north, leg_east) = (mark_north - from_north, mark_east - from_east);
+        let cross_track_error = (leg_north * (self.east - from_east)
+            - leg_east * (self.north - from_north))
+            / leg_north.hypot(leg_east);
+
+        let depth = self.depth() + 0.05 * self.random.normal();
+        let battery_load = BATTERY_BASE_LOAD + 8.0 * self.rudder.abs() + 0.3 * self.random.normal();
+        self.battery_used_ah += battery_load * dt / 3600.0;
+        let state_of_charge = (1.0 - self.battery_used_ah / BATTERY_CAPACITY_AH).max(0.0);
+        let air_temperature = self.air_temperature.step(&mut self.random, dt);
+        let pressure = self.pressure.step(&mut self.random, dt);
+        let water_temperature = 15.0 + 0.4 * (self.time / 1800.0).sin();
+        let variation = MAGNETIC_VARIATION_DEGREES.to_radians();
+        let (latitude, longitude) = self.position();
+
+        let values: Vec<(&str, Value)> = vec![
+            (
+                "navigation.position",
+                json!({"latitude": latitude, "longitude": longitude}),
+            ),
+            ("navigation.datetime", json!(self.datetime())),
+            ("navigation.headingTrue", json!(self.heading)),
+            (
+                "navigation.headingMagnetic",
+                json!((self.heading - variation).rem_euclid(TAU)),
+            ),
+            ("navigation.magneticVariation", json!(variation)),
+            ("navigation.speedThroughWater", json!(self.speed)),
+            ("navigation.speedOverGround", json!(speed_over_ground)),
+            ("navigation.courseOverGroundTrue", json!(course_over_ground)),
+            ("navigation.log", json!(self.log)),
+            ("navigation.trip.log", json!(self.trip_log)),
+            (
+                "navigation.course.calcValues.bearingTrue",
+                json!(bearing.rem_euclid(TAU)),
+            ),
+            ("navigation.course.calcValues.distance", json!(distance)),
+            (
+                "navigation.course.calcValues.velocityMadeGood",
+                json!(speed_over_ground * (course_over_ground - bearing).cos()),
+            ),
+            (
+                "navigation.course.calcValues.crossTrackError",
+                json!(cross_track_error),
+            ),
+            (
+                "performance.velocityMadeGood",
+                json!(self.speed * true_wind_angle.cos()),
+            ),
+            ("steering.rudderAngle", json!(self.rudder)),
+            ("environment.wind.angleApparent", json!(apparent_angle)),
+            ("environment.wind.speedApparent", json!(apparent_speed)),
+            ("environment.wind.angleTrueWater", json!(true_wind_angle)),
+            ("environment.wind.speedTrue", json!(water_wind_speed)),
+            (
+                "environment.wind.angleTrueGround",
+                json!(normalize(wind_direction - self.heading)),
+            ),
+            (
+                "environment.wind.directionTrue",
+                json!(wind_direction.rem_euclid(TAU)),
+            ),
+            (
+                "environment.wind.directionMagnetic",
+                json!((wind_direction - variation).rem_euclid(TAU)),
+            ),
+            ("environment.wind.speedOverGround", json!(wind_speed)),
+            ("environment.current.setTrue", json!(current_set)),
+            ("environment.current.drift", json!(CURRENT_DRIFT)),
+            ("environment.depth.belowSurface", json!(depth)),
+            (
+                "environment.depth.belowTransducer",
+                json!(depth - TRANSDUCER_DEPTH),
+            ),
+            (
+                "environment.depth.belowKeel",
+                json!(depth - TRANSDUCER_DEPTH - KEEL_BELOW_TRANSDUCER),
+            ),
+            (
+                "environment.water.temperature",
+                json!(water_temperature + KELVIN_AT_ZERO_CELSIUS),
+            ),
+            (
+                "environment.outside.temperature",
+                json!(air_temperature + KELVIN_AT_ZERO_CELSIUS),
+            ),
+            ("environment.outside.pressure", json!(pressure)),
+            (
+                "electrical.batteries.house.voltage",
+                json!(11.9 + state_of_charge - 0.02 * battery_load),
+            ),
+            ("electrical.batteries.house.current", json!(-battery_load)),
+            (
+                "electrical.batteries.house.capacity.stateOfCharge",
+                json!(state_of_charge),
+            ),
+        ];
+        let update = values
+            .into_iter()
+            .fold(
+                V1UpdateType::builder().ref_source("simulator".to_string()),
+                |builder, (path, value)| {
+                    builder.add_update(V1UpdateValue::new(path.to_string(), value))
+                },
+            )
+            .build();
+        V1DeltaFormat::builder()
+            .context(LOCAL_CONTEXT.to_string())
+            .add_update(update)
+            .build()
+    }
+
+    /// The depth below the surface, shoals and deeps that depend only on where the boat is.
+    fn depth(&self) -> f64 {
+        let depth = 12.0
+            + 6.0 * (self.north / 400.0).sin()
+            + 5.0 * (self.east / 350.0).cos()
+            + 3.0 * ((self.north + self.east) / 150.0).sin();
+        depth.max(3.0)
+    }
+
+    /// Latitude and longitude in degrees.
+    fn position(&self) -> (f64, f64) {
+        let latitude = START_LATITUDE + (self.north / EARTH_RADIUS).to_degrees();
+        let longitude = START_LONGITUDE
+            + (self.east / (EARTH_RADIUS * START_LATITUDE.to_radians().cos())).to_degrees();
+        (latitude, longitude)
+    }
+
+    /// The simulated time as an ISO 8601 UTC date and time.
+    fn datetime(&self) -> String {
+        let seconds = (START_TIME + self.time) as i64;
+        let (days, second_of_day) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z",
+            year,
+            month,
+            day,
+            second_of_day / 3600,
+            second_of_day % 3600 / 60,
+            second_of_day % 60
+        )
+    }
+}
+
+/// The boat speed for a true wind speed and angle off the bow, from 0 to PI.
+fn polar_speed(wind_speed: f64, wind_angle: f64) -> f64 {
+    // The fraction of the best speed at some angles, in degrees, straight lines in between.
+    const POLAR: [(f64, f64); 5] = [
+        (0.0, 0.0),
+        (30.0, 0.0),
+        (45.0, 0.8),
+        (100.0, 1.0),
+        (180.0, 0.8),
+    ];
+    let angle = wind_angle.to_degrees();
+    let angle_factor =
+        POLAR
+            .windows(2)
+            .find(|pair| angle <= pair[1].0)
+            .map_or(POLAR[POLAR.len() - 1].1, |pair| {
+                let ((from_angle, from), (to_angle, to)) = (pair[0], pair[1]);
+                from + (to - from) * (angle - from_angle) / (to_angle - from_angle)
+            });
+    (wind_speed * 0.6).min(HULL_SPEED) * angle_factor
+}
+
+/// An angle between -PI and PI.
+fn normalize(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+/// The year, month and day of a day since the Unix epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The simulator as a data source, ticking along with the wall clock.
+pub(crate) struct SimulatorSource {
+    /// The simulator, or why the options in the address can't be used.
+    simulator: Result<Simulator, String>,
+    started: Instant,
+    ticks: u32,
+}
+
+impl SimulatorSource {
+    /// Simulate from an address like `?seed=42`, the seed defaults to 1.
+    pub(crate) fn open(address: &str) -> Self {
+        let query = address.trim_start_matches('/').trim_start_matches('?');
+        let simulator = match query.split_once('=') {
+            None if query.is_empty() => Ok(Simulator::new(1)),
+            Some(("seed", seed)) => seed
+                .parse::<u64>()
+                .map(Simulator::new)
+                .map_err(|_| format!("{} isn't a simulator seed", seed)),
+            _ => Err(format!(
+                "Unknown simulator option {}, use seed=<number>",
+                query
+            )),
+        };
+        Self {
+            simulator,
+            started: Instant::now(),
+            ticks: 0,
+        }
+    }
+}
+
+impl DeltaSource for SimulatorSource {
+    fn recv_deltas(&mut self) -> Result<Vec<V1DeltaFormat>, String> {
+        let simulator = self.simulator.as_mut().map_err(|reason| reason.clone())?;
+        let due = (self.started.elapsed().as_secs_f64() / TICK.as_secs_f64()) as u32 + 1;
+        // Skip what a paused display missed, instead of simulating it all at once.
+        self.ticks = self.ticks.max(due.saturating_sub(MAX_TICKS_PER_CALL));
+        let ticks = due - self.ticks;
+        self.ticks = due;
+        Ok((0..ticks).map(|_| simulator.tick()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64, ticks: usize) -> Vec<Value> {
+        let mut simulator = Simulator::new(seed);
+        (0..ticks)
+            .map(|_| serde_json::to_value(simulator.tick()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_deltas() {
+        assert_eq!(run(7, 600), run(7, 600));
+    }
+
+    #[test]
+    fn other_seed_gives_other_deltas() {
+        assert_ne!(run(7, 60), run(8, 60));
+    }
+
+    #[test]
+    fn starts_at_a_fixed_time() {
+        let delta = &run(1, 1)[0];
+        let datetime = delta["updates"][0]["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|value| value["path"] == "navigation.datetime")
+            .map(|value| value["value"].clone());
+        assert_eq!(datetime, Some(json!("2024-06-15T10:00:01.000Z")));
+    }
+}
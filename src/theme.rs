@@ -0,0 +1,179 @@
+use eframe::egui;
+use egui::{Color32, Stroke, Visuals};
+use web_time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::communication::{PathSubscription, SignalKCommunicator, SubscriptionPolicy};
+
+/// The colour schemes for the display, for use from bright daylight to a dark cockpit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum DisplayTheme {
+    #[default]
+    Day,
+    Dusk,
+    /// Red on black only, to keep the crew's night vision.
+    Night,
+}
+
+/// Colours used by the layouts when drawing a page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// The big value digits.
+    pub value: Color32,
+    /// Abbreviations, units and names.
+    pub label: Color32,
+    /// Outlines of cells, dials and tick marks.
+    pub frame: Color32,
+    pub port: Color32,
+    pub starboard: Color32,
+    /// Needles and markers.
+    pub accent: Color32,
+}
+
+impl DisplayTheme {
+    pub const ALL: [DisplayTheme; 3] = [DisplayTheme::Day, DisplayTheme::Dusk, DisplayTheme::Night];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayTheme::Day => "Day",
+            DisplayTheme::Dusk => "Dusk",
+            DisplayTheme::Night => "Night",
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        match self {
+            DisplayTheme::Day => Palette {
+                value: Color32::BLACK,
+                label: Color32::from_gray(60),
+                frame: Color32::GRAY,
+                port: Color32::from_rgb(210, 0, 0),
+                starboard: Color32::from_rgb(0, 150, 0),
+                accent: Color32::from_rgb(0, 70, 200),
+            },
+            DisplayTheme::Dusk => Palette {
+                value: Color32::from_gray(220),
+                label: Color32::from_gray(150),
+                frame: Color32::from_gray(80),
+                port: Color32::from_rgb(190, 40, 40),
+                starboard: Color32::from_rgb(40, 160, 40),
+                accent: Color32::from_rgb(230, 180, 0),
+            },
+            DisplayTheme::Night => Palette {
+                value: Color32::from_rgb(210, 0, 0),
+                label: Color32::from_rgb(140, 0, 0),
+                frame: Color32::from_rgb(80, 0, 0),
+                port: Color32::from_rgb(200, 0, 0),
+                starboard: Color32::from_rgb(100, 0, 0),
+                accent: Color32::from_rgb(255, 40, 40),
+            },
+        }
+    }
+
+    pub fn visuals(&self) -> Visuals {
+        match self {
+            DisplayTheme::Day => Visuals::light(),
+            DisplayTheme::Dusk => Visuals::dark(),
+            DisplayTheme::Night => {
+                let palette = self.palette();
+                let mut visuals = Visuals::dark();
+                visuals.override_text_color = Some(palette.label);
+                visuals.hyperlink_color = palette.value;
+                visuals.panel_fill = Color32::BLACK;
+                visuals.window_fill = Color32::BLACK;
+                visuals.extreme_bg_color = Color32::BLACK;
+                visuals.faint_bg_color = Color32::from_rgb(20, 0, 0);
+                visuals.window_stroke = Stroke::new(1.0, palette.frame);
+                visuals.selection.bg_fill = Color32::from_rgb(70, 0, 0);
+                visuals.selection.stroke = Stroke::new(1.0, palette.value);
+                for widget in [
+                    &mut visuals.widgets.noninteractive,
+                    &mut visuals.widgets.inactive,
+                    &mut visuals.widgets.hovered,
+                    &mut visuals.widgets.active,
+                    &mut visuals.widgets.open,
+                ] {
+                    widget.bg_fill = Color32::from_rgb(25, 0, 0);
+                    widget.weak_bg_fill = Color32::from_rgb(25, 0, 0);
+                    widget.bg_stroke = Stroke::new(widget.bg_stroke.width, palette.frame);
+                    widget.fg_stroke = Stroke::new(widget.fg_stroke.width, palette.label);
+                }
+                visuals
+            }
+        }
+    }
+
+    /// Set the egui visuals for this theme, whatever the system light or dark preference is.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_visuals_of(egui::Theme::Dark, self.visuals());
+        ctx.set_visuals_of(egui::Theme::Light, self.visuals());
+    }
+}
+
+/// Pick a theme from the SignalK `environment.sun` mode, or if the server doesn't
+/// provide it, from the sun elevation at the current position.
+pub fn automatic_theme(communicator: &SignalKCommunicator) -> Option<DisplayTheme> {
+    if let Some(sun) = communicator.get_string_for_path("self.environment.sun") {
+        match sun.as_str() {
+            "day" => return Some(DisplayTheme::Day),
+            "dawn" | "sunrise" | "sunset" | "dusk" => return Some(DisplayTheme::Dusk),
+            "night" => return Some(DisplayTheme::Night),
+            _ => log::debug!("Unknown environment.sun value {:?}", sun),
+        }
+    }
+    let (latitude, longitude) = communicator.get_self_position()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let elevation = sun_elevation(now.as_secs_f64(), latitude, longitude);
+    Some(theme_for_sun_elevation(elevation))
+}
+
+/// The updates `automatic_theme` needs from the server.
+pub fn theme_subscriptions() -> Vec<PathSubscription> {
+    ["self.environment.sun", "self.navigation.position"]
+        .into_iter()
+        .map(|path| {
+            PathSubscription::new(path.to_string(), Duration::from_secs(60))
+                .with_policy(SubscriptionPolicy::Fixed)
+        })
+        .collect()
+}
+
+/// Day while the sun is above the horizon, dusk during civil twilight and night after that.
+fn theme_for_sun_elevation(elevation: f64) -> DisplayTheme {
+    // Sunrise and sunset are when the upper limb, with refraction, touches the horizon.
+    const HORIZON: f64 = -0.833;
+    const CIVIL_TWILIGHT: f64 = -6.0;
+    if elevation > HORIZON {
+        DisplayTheme::Day
+    } else if elevation > CIVIL_TWILIGHT {
+        DisplayTheme::Dusk
+    } else {
+        DisplayTheme::Night
+    }
+}
+
+/// The elevation of the sun in degrees at a unix time and position, using the low precision
+/// formulas from the Astronomical Almanac, good to about a degree.
+fn sun_elevation(unix_secs: f64, latitude: f64, longitude: f64) -> f64 {
+    const J2000_UNIX_SECS: f64 = 946_728_000.0;
+    let days = (unix_secs - J2000_UNIX_SECS) / 86_400.0;
+
+    let mean_anomaly = (357.529 + 0.985_600_28 * days).to_radians();
+    let mean_longitude = 280.459 + 0.985_647_36 * days;
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_36 * days).to_radians();
+
+    let right_ascension =
+        (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    let sidereal_degrees =
+        (280.460_618_37 + 360.985_647_366_29 * days + longitude).rem_euclid(360.0);
+    let hour_angle = sidereal_degrees.to_radians() - right_ascension;
+
+    let latitude = latitude.to_radians();
+    (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos())
+        .asin()
+        .to_degrees()
+}